@@ -0,0 +1,109 @@
+// Benchmarks for the parts of a render that decide its speed: building the BVH, tracing rays
+// through it, Perlin noise, and small renders of whole scenes. Run with
+//
+//     cargo bench
+//
+// and compare against a saved run with --save-baseline and --baseline to see what a change did.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use raytracer::bvh::BVHNode;
+use raytracer::hit::{HitRecord, Hittable};
+use raytracer::interval::Interval;
+use raytracer::material::Materials;
+use raytracer::perlin::Perlin;
+use raytracer::ray::Ray;
+use raytracer::rtweekend::{random_double, seed_random};
+use raytracer::scenes;
+use raytracer::vec3::Vec3;
+type Point3 = Vec3;
+
+const SEED: u64 = 2024;
+const RAYS: usize = 10_000;
+
+fn bvh_build(c: &mut Criterion) {
+    seed_random(SEED);
+    let mut materials = Materials::new();
+    let boxes = scenes::final_scene_boxes(&mut materials);
+    let spheres = scenes::final_scene_spheres(&mut materials);
+
+    let mut group = c.benchmark_group("bvh_build");
+    for (name, objects) in [("final_scene_boxes", &boxes), ("final_scene_spheres", &spheres)] {
+        group.throughput(Throughput::Elements(objects.objects.len() as u64));
+        // The build sorts the list it is given, so each run gets a fresh copy.
+        group.bench_function(name, |b| b.iter_batched(|| objects.clone(), |mut objects| BVHNode::new(&mut objects), BatchSize::SmallInput));
+    }
+    group.finish();
+}
+
+fn random_rays(from: impl Fn() -> Point3, towards: impl Fn() -> Point3) -> Vec<Ray> {
+    (0..RAYS)
+        .map(|_| {
+            let origin = from();
+            Ray::new(origin, towards() - origin, 0.0)
+        })
+        .collect()
+}
+
+fn bvh_traversal(c: &mut Criterion) {
+    seed_random(SEED);
+    let mut materials = Materials::new();
+    let boxes = BVHNode::new(&mut scenes::final_scene_boxes(&mut materials));
+    let spheres = BVHNode::new(&mut scenes::final_scene_spheres(&mut materials));
+    // Down onto the ground from above it, and into the cube of spheres from all around it.
+    let ground_rays = random_rays(
+        || Point3::new(random_double(-1000.0, 1000.0), 300.0, random_double(-1000.0, 1000.0)),
+        || Point3::new(random_double(-1000.0, 1000.0), 0.0, random_double(-1000.0, 1000.0)),
+    );
+    let sphere_rays = random_rays(|| Point3::new(82.5, 82.5, 82.5) + 400.0 * Vec3::random_unit_vector(), || Point3::random(0.0, 165.0));
+
+    // Reported per ray, so criterion's throughput is the rays per second.
+    let mut group = c.benchmark_group("bvh_traversal");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    for (name, bvh, rays) in [("final_scene_boxes", &boxes, &ground_rays), ("final_scene_spheres", &spheres, &sphere_rays)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut hits = 0;
+                for r in rays {
+                    let mut rec = HitRecord::default();
+                    if bvh.hit(r, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+                        hits += 1;
+                    }
+                }
+                black_box(hits)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn perlin_turb(c: &mut Criterion) {
+    seed_random(SEED);
+    let perlin = Perlin::with_seed(SEED);
+    let points: Vec<Point3> = (0..RAYS).map(|_| Point3::random(-10.0, 10.0)).collect();
+
+    let mut group = c.benchmark_group("perlin");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    group.bench_function("turb", |b| b.iter(|| points.iter().map(|p| perlin.turb(p, 7)).sum::<f64>()));
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    // Small enough to run in a second or so, so that criterion can take a handful of samples.
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for description in ["cornell_box", "perlin_spheres", "checkered_spheres", "final_scene 64 64 4 8"] {
+        seed_random(SEED);
+        let mut scene = scenes::from_description(description).unwrap();
+        let camera = &mut scene.camera;
+        camera.set_image_size(64, 64);
+        camera.samples_per_pixel = camera.samples_per_pixel.min(4);
+        camera.max_depth = camera.max_depth.min(8);
+        camera.seed = Some(SEED);
+        group.throughput(Throughput::Elements(64 * 64 * camera.samples_per_pixel as u64));
+        group.bench_function(description.split_whitespace().next().unwrap(), |b| b.iter(|| scene.render()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bvh_build, bvh_traversal, perlin_turb, render);
+criterion_main!(benches);
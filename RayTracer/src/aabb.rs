@@ -10,6 +10,12 @@ pub struct AABB {
     pub z: Interval,
 }
 
+impl Default for AABB {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AABB {
     pub fn new() -> Self {
         AABB {
@@ -65,8 +71,19 @@ impl AABB {
         }
     }
 
+    pub fn contains(&self, p: Point3, slack: f64) -> bool {
+        // Whether p is in the box grown by slack on every side.
+        let within = |interval: &Interval, x: f64| interval.min - slack <= x && x <= interval.max + slack;
+        within(&self.x, p.x) && within(&self.y, p.y) && within(&self.z, p.z)
+    }
+
     pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
-        let mut ray_t = ray_t.clone();
+        self.clip(r, ray_t).is_some()
+    }
+
+    pub fn clip(&self, r: &Ray, ray_t: Interval) -> Option<Interval> {
+        // Returns the part of ray_t that lies inside the box, or None if the ray misses it.
+        let mut ray_t = ray_t;
         let ray_orig = r.origin();
         let ray_dir = r.direction();
 
@@ -104,10 +121,10 @@ impl AABB {
             }
 
             if ray_t.max <= ray_t.min {
-                return false;
+                return None;
             }
         }
-        true
+        Some(ray_t)
     }
 
     pub fn longest_axis(&self) -> usize {
@@ -163,3 +180,68 @@ impl std::ops::Add<AABB> for Vec3 {
         bbox + self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtweekend::{random_double, seed_random};
+
+    fn random_box() -> AABB {
+        AABB::from_points(Point3::random(-5.0, 5.0), Point3::random(-5.0, 5.0))
+    }
+
+    fn random_point_in(bbox: &AABB) -> Point3 {
+        Point3::new(random_double(bbox.x.min, bbox.x.max), random_double(bbox.y.min, bbox.y.max), random_double(bbox.z.min, bbox.z.max))
+    }
+
+    #[test]
+    fn test_rays_through_interior_hit() {
+        seed_random(45);
+        for _ in 0..1000 {
+            let bbox = random_box();
+            let target = random_point_in(&bbox);
+            let origin = Point3::random(-20.0, 20.0);
+            let r = Ray::new(origin, target - origin, 0.0);
+
+            let span = bbox.clip(&r, Interval::with_values(0.0, f64::INFINITY)).expect("ray through the box missed it");
+            // The ray reaches the target at t = 1, which must lie in the clipped span, and the
+            // ends of the span are on the box.
+            assert!(span.min <= 1.0 + 1e-9 && 1.0 - 1e-9 <= span.max);
+            assert!(bbox.contains(r.at(span.min), 1e-9));
+            assert!(bbox.contains(r.at(span.max), 1e-9));
+            assert!(bbox.contains(r.at(0.5 * (span.min + span.max)), 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_rays_pointing_away_miss() {
+        seed_random(46);
+        for _ in 0..1000 {
+            let bbox = random_box();
+            // From a corner region outside the box, moving further out on every axis.
+            let origin = Point3::new(bbox.x.max + random_double(0.1, 5.0), bbox.y.max + random_double(0.1, 5.0), bbox.z.min - random_double(0.1, 5.0));
+            let direction = Vec3::new(random_double(0.0, 1.0), random_double(0.0, 1.0), -random_double(0.0, 1.0));
+            assert!(!bbox.hit(&Ray::new(origin, direction, 0.0), Interval::with_values(0.0, f64::INFINITY)));
+        }
+    }
+
+    #[test]
+    fn test_ray_interval_limits_hits() {
+        let bbox = AABB::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        // Axis aligned, so two of the direction components are zero.
+        let span = bbox.clip(&r, Interval::with_values(0.0, f64::INFINITY)).unwrap();
+        assert_eq!((span.min, span.max), (4.0, 6.0));
+        assert!(!bbox.hit(&r, Interval::with_values(0.0, 3.9)));
+        assert!(!bbox.hit(&r, Interval::with_values(6.1, 10.0)));
+        assert!(bbox.hit(&r, Interval::with_values(4.5, 5.0)));
+    }
+
+    #[test]
+    fn test_thin_boxes_are_padded() {
+        let bbox = AABB::from_points(Point3::new(0.0, 2.0, 0.0), Point3::new(1.0, 2.0, 1.0));
+        assert!((bbox.y.size() - 0.0001).abs() < 1e-12);
+        let r = Ray::new(Point3::new(0.5, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(bbox.hit(&r, Interval::with_values(0.0, f64::INFINITY)));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::framebuffer::FrameBuffer;
+use crate::vec3::Vec3;
+type Color = Vec3;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 4] = b"RTAC";
+const VERSION: u32 = 2;
+const HAS_VARIANCE: u32 = 1;
+// Magic bytes, version, width, height, flags and scene.
+const HEADER_LEN: u64 = 4 + 4 * 4 + 8;
+
+// Raw samples of a render before they are averaged: the radiance sum and sample count of every
+// pixel, and optionally the sum of squares for the variance. Renders of the same scene made apart,
+// on other machines or in other processes, merge into one image with all their samples by adding
+// these up, which averaging the finished images can't do when they took different sample counts.
+#[derive(Clone)]
+pub struct Accumulation {
+    width: u32,
+    height: u32,
+    scene: u64, // Scene::fingerprint of what the samples are of, or 0 if unknown
+    sums: Vec<Color>,
+    counts: Vec<u64>,
+    squared_sums: Option<Vec<Color>>, // Per channel sum of squared samples, when the variance is tracked
+}
+
+impl Accumulation {
+    pub fn new(width: u32, height: u32, track_variance: bool) -> Self {
+        let pixels = pixel_count(width, height).expect("too many pixels to accumulate");
+        Accumulation {
+            width,
+            height,
+            scene: 0,
+            sums: vec![Color::zero(); pixels],
+            counts: vec![0; pixels],
+            squared_sums: if track_variance { Some(vec![Color::zero(); pixels]) } else { None },
+        }
+    }
+
+    pub fn with_scene(mut self, scene: u64) -> Self {
+        self.scene = scene;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn scene(&self) -> u64 {
+        self.scene
+    }
+
+    pub fn tracks_variance(&self) -> bool {
+        self.squared_sums.is_some()
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        j as usize * self.width as usize + i as usize
+    }
+
+    pub fn add_sample(&mut self, i: u32, j: u32, color: Color) {
+        let index = self.index(i, j);
+        self.sums[index] += color;
+        self.counts[index] += 1;
+        if let Some(squared_sums) = &mut self.squared_sums {
+            squared_sums[index] += color * color;
+        }
+    }
+
+    pub fn sum(&self, i: u32, j: u32) -> Color {
+        self.sums[self.index(i, j)]
+    }
+
+    pub fn count(&self, i: u32, j: u32) -> u64 {
+        self.counts[self.index(i, j)]
+    }
+
+    pub fn mean(&self, i: u32, j: u32) -> Color {
+        let index = self.index(i, j);
+        if self.counts[index] == 0 {
+            return Color::zero();
+        }
+        self.sums[index] / self.counts[index] as f64
+    }
+
+    pub fn variance(&self, i: u32, j: u32) -> Option<Color> {
+        // Unbiased per channel variance of the pixel's samples, zero with fewer than two.
+        let squared_sums = self.squared_sums.as_ref()?;
+        let index = self.index(i, j);
+        let n = self.counts[index] as f64;
+        if n < 2.0 {
+            return Some(Color::zero());
+        }
+        let mean = self.sums[index] / n;
+        let variance = (squared_sums[index] - n * (mean * mean)) / (n - 1.0);
+        // Rounding can leave a tiny negative where all the samples agree.
+        Some(Color::new(variance.x.max(0.0), variance.y.max(0.0), variance.z.max(0.0)))
+    }
+
+    pub fn add_at(&mut self, x0: u32, y0: u32, other: &Accumulation) {
+        // Adds the samples of a smaller accumulation, such as one tile, with its top left corner
+        // at x0, y0.
+        for j in 0..other.height {
+            for i in 0..other.width {
+                let from = other.index(i, j);
+                let to = self.index(x0 + i, y0 + j);
+                self.sums[to] += other.sums[from];
+                self.counts[to] += other.counts[from];
+                if let (Some(to_squared), Some(from_squared)) = (&mut self.squared_sums, &other.squared_sums) {
+                    to_squared[to] += from_squared[from];
+                }
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &Accumulation) -> io::Result<()> {
+        // Adds the samples of another render of the same image. The variance is kept only if
+        // both sides track it.
+        if self.scene != other.scene {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot merge renders of different scenes ({:016x} and {:016x})", other.scene, self.scene),
+            ));
+        }
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot merge a {}x{} render into a {}x{} one", other.width, other.height, self.width, self.height),
+            ));
+        }
+        for (sum, other_sum) in self.sums.iter_mut().zip(&other.sums) {
+            *sum += *other_sum;
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += *other_count;
+        }
+        match (&mut self.squared_sums, &other.squared_sums) {
+            (Some(squared_sums), Some(other_squared_sums)) => {
+                for (squared_sum, other_squared_sum) in squared_sums.iter_mut().zip(other_squared_sums) {
+                    *squared_sum += *other_squared_sum;
+                }
+            }
+            _ => self.squared_sums = None,
+        }
+        Ok(())
+    }
+
+    pub fn to_framebuffer(&self) -> FrameBuffer {
+        let mut image = FrameBuffer::new(self.width, self.height);
+        for j in 0..self.height {
+            for i in 0..self.width {
+                image.set(i, j, self.mean(i, j));
+            }
+        }
+        image
+    }
+
+    // The file holds a header of the magic bytes, version, width, height, flags and scene, then for
+    // every pixel row by row its count, sum and, with the variance flag, sum of squares. Numbers
+    // are little endian.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let flags = if self.tracks_variance() { HAS_VARIANCE } else { 0 };
+        writer.write_all(MAGIC)?;
+        for value in [VERSION, self.width, self.height, flags] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.scene.to_le_bytes())?;
+        for index in 0..self.counts.len() {
+            writer.write_all(&self.counts[index].to_le_bytes())?;
+            write_color(writer, self.sums[index])?;
+            if let Some(squared_sums) = &self.squared_sums {
+                write_color(writer, squared_sums[index])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let (width, height, flags, scene) = read_header(reader)?;
+        Self::read_samples(reader, width, height, flags, scene)
+    }
+
+    // Like read_from, but fails before allocating anything unless the samples are width by height,
+    // for reading from a peer that may send anything.
+    pub fn read_from_expecting(reader: &mut impl Read, width: u32, height: u32) -> io::Result<Self> {
+        let (got_width, got_height, flags, scene) = read_header(reader)?;
+        if (got_width, got_height) != (width, height) {
+            return Err(invalid_data(format!("expected {}x{} samples, got {}x{}", width, height, got_width, got_height)));
+        }
+        Self::read_samples(reader, width, height, flags, scene)
+    }
+
+    fn read_samples(reader: &mut impl Read, width: u32, height: u32, flags: u32, scene: u64) -> io::Result<Self> {
+        if pixel_count(width, height).is_none() {
+            return Err(invalid_data(format!("{}x{} samples are too many to hold", width, height)));
+        }
+        let mut accumulation = Accumulation::new(width, height, flags & HAS_VARIANCE != 0).with_scene(scene);
+        for index in 0..accumulation.counts.len() {
+            let mut count = [0; 8];
+            reader.read_exact(&mut count)?;
+            accumulation.counts[index] = u64::from_le_bytes(count);
+            accumulation.sums[index] = read_color(reader)?;
+            if let Some(squared_sums) = &mut accumulation.squared_sums {
+                squared_sums[index] = read_color(reader)?;
+            }
+        }
+        Ok(accumulation)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        // The header is checked against the length of the file before anything is allocated, so a
+        // damaged or made up file can't claim more samples than it holds.
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let (width, height, flags, scene) = read_header(&mut reader)?;
+        if encoded_len(width, height, flags) != Some(file_len) {
+            return Err(invalid_data(format!("a file of {} bytes can't hold {}x{} samples", file_len, width, height)));
+        }
+        Self::read_samples(&mut reader, width, height, flags, scene)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn pixel_count(width: u32, height: u32) -> Option<usize> {
+    // Also checks that the pixels' sums can be addressed, so the allocation fails cleanly instead.
+    let pixels = (width as usize).checked_mul(height as usize)?;
+    pixels.checked_mul(std::mem::size_of::<Color>())?;
+    Some(pixels)
+}
+
+fn encoded_len(width: u32, height: u32, flags: u32) -> Option<u64> {
+    // Bytes written by write_to: a count and a sum for every pixel, and a sum of squares with the
+    // variance.
+    let pixel_len = if flags & HAS_VARIANCE != 0 { 8 + 24 + 24 } else { 8 + 24 };
+    (u64::from(width) * u64::from(height)).checked_mul(pixel_len)?.checked_add(HEADER_LEN)
+}
+
+fn read_header(reader: &mut impl Read) -> io::Result<(u32, u32, u32, u64)> {
+    // Width, height, flags and scene.
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a raw render accumulation".to_string()));
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(invalid_data(format!("unsupported accumulation version {}", version)));
+    }
+    let (width, height, flags) = (read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+    let mut scene = [0; 8];
+    reader.read_exact(&mut scene)?;
+    Ok((width, height, flags, u64::from_le_bytes(scene)))
+}
+
+fn write_color(writer: &mut impl Write, color: Color) -> io::Result<()> {
+    for value in [color.x, color.y, color.z] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_color(reader: &mut impl Read) -> io::Result<Color> {
+    let mut values = [0.0; 3];
+    for value in values.iter_mut() {
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        *value = f64::from_le_bytes(bytes);
+    }
+    Ok(Color::new(values[0], values[1], values[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for track_variance in [false, true] {
+            let mut accumulation = Accumulation::new(3, 2, track_variance).with_scene(0x1234_5678_9abc_def0);
+            accumulation.add_sample(0, 0, Color::new(1.0, 2.0, 3.0));
+            accumulation.add_sample(2, 1, Color::new(0.5, 0.25, 0.125));
+            accumulation.add_sample(2, 1, Color::new(1.5, 0.75, 0.375));
+
+            let mut bytes = Vec::new();
+            accumulation.write_to(&mut bytes).unwrap();
+            let read = Accumulation::read_from(&mut bytes.as_slice()).unwrap();
+
+            assert_eq!((read.width(), read.height()), (3, 2));
+            assert_eq!(read.scene(), 0x1234_5678_9abc_def0);
+            assert_eq!(read.tracks_variance(), track_variance);
+            assert_eq!(read.counts, accumulation.counts);
+            assert_eq!(read.sums, accumulation.sums);
+            assert_eq!(read.squared_sums, accumulation.squared_sums);
+        }
+
+        assert!(Accumulation::read_from(&mut &b"JPEG and more"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_expecting_checks_size_first() {
+        let mut bytes = Vec::new();
+        Accumulation::new(3, 2, false).write_to(&mut bytes).unwrap();
+        assert!(Accumulation::read_from_expecting(&mut bytes.as_slice(), 3, 2).is_ok());
+        assert!(Accumulation::read_from_expecting(&mut bytes.as_slice(), 2, 3).is_err());
+
+        // A header claiming a huge image is refused without reading or allocating for it.
+        let mut header = MAGIC.to_vec();
+        for value in [VERSION, u32::MAX, u32::MAX, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&0u64.to_le_bytes());
+        let error = Accumulation::read_from_expecting(&mut header.as_slice(), 3, 2).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_load_checks_size_against_file() {
+        let path = std::env::temp_dir().join(format!("accumulation-{}.acc", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut accumulation = Accumulation::new(3, 2, true);
+        accumulation.add_sample(1, 1, Color::new(1.0, 2.0, 3.0));
+        accumulation.save(path).unwrap();
+        assert_eq!(std::fs::metadata(path).unwrap().len(), encoded_len(3, 2, HAS_VARIANCE).unwrap());
+        assert_eq!(Accumulation::load(path).unwrap().count(1, 1), 1);
+
+        // The same header claiming a huge image is refused rather than allocated for.
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[8..16].copy_from_slice(&[0xff; 8]);
+        std::fs::write(path, &bytes).unwrap();
+        let error = Accumulation::load(path).err().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_merge_weights_by_sample_count() {
+        // One sample of 1 and three of 3 average to 2.5, not to the 2 of averaging the two images.
+        let mut a = Accumulation::new(1, 1, true);
+        a.add_sample(0, 0, Color::new(1.0, 1.0, 1.0));
+        let mut b = Accumulation::new(1, 1, true);
+        for _ in 0..3 {
+            b.add_sample(0, 0, Color::new(3.0, 3.0, 3.0));
+        }
+
+        a.merge(&b).unwrap();
+        assert_eq!(a.count(0, 0), 4);
+        assert!((a.mean(0, 0) - Color::new(2.5, 2.5, 2.5)).length() < 1e-12);
+        // Samples 1, 3, 3, 3: squared deviations sum to 2.25 + 3 * 0.25 = 3, over n - 1 = 3.
+        assert!((a.variance(0, 0).unwrap() - Color::new(1.0, 1.0, 1.0)).length() < 1e-12);
+
+        // Merging with a render that doesn't track the variance drops it.
+        a.merge(&Accumulation::new(1, 1, false)).unwrap();
+        assert!(a.variance(0, 0).is_none());
+
+        assert!(a.merge(&Accumulation::new(2, 1, false)).is_err());
+    }
+
+    #[test]
+    fn test_merge_refuses_other_scenes() {
+        let mut a = Accumulation::new(1, 1, false).with_scene(1);
+        assert!(a.merge(&Accumulation::new(1, 1, false).with_scene(1)).is_ok());
+        let error = a.merge(&Accumulation::new(1, 1, false).with_scene(2)).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(a.merge(&Accumulation::new(1, 1, false)).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+use crate::hit::{HitRecord, Hittable};
+use crate::hit_list::HittableList;
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::interval::Interval;
+use crate::light::Light;
+use crate::light_sampler::EmissionSampler;
+use crate::material::{Isotropic, Materials, ScatterRecord};
+use crate::pdf::{Pdf, ScatterPdf};
+use crate::ray::Ray;
+use crate::stats::{count, Counter};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light, // First vertex of a light path, on the surface of an emitter
+    Surface,
+    Medium, // Scattering inside a participating medium, which has no normal
+}
+
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    p: Point3,
+    normal: Vec3, // Unit geometric normal, zero for the camera and in media
+    rec: HitRecord,
+    r_in: Ray, // Ray that arrived at the vertex
+    attenuation: Color,
+    pdf: Option<ScatterPdf>, // Scattering distribution, None where the vertex can't be connected to
+    beta: Color, // Path throughput up to this vertex, divided by the densities of sampling it
+    delta: bool, // Scatters specularly, so only the sampled direction carries light
+    pdf_fwd: f64, // Area density of sampling this vertex from the previous one on its own path
+    pdf_rev: f64, // Area density of sampling it from the next one, walking the path the other way
+    emitted: Color, // Light leaving towards the previous vertex, for camera paths that hit an emitter
+}
+
+impl Vertex {
+    fn camera(r: &Ray) -> Self {
+        Vertex {
+            kind: VertexKind::Camera,
+            p: *r.origin(),
+            normal: Vec3::zero(),
+            rec: HitRecord::default(),
+            r_in: *r,
+            attenuation: Color::zero(),
+            pdf: None,
+            beta: Color::new(1.0, 1.0, 1.0),
+            delta: false,
+            pdf_fwd: 1.0,
+            pdf_rev: 0.0,
+            emitted: Color::zero(),
+        }
+    }
+
+    fn is_connectible(&self) -> bool {
+        !self.delta && (self.kind == VertexKind::Light || self.pdf.is_some())
+    }
+
+    fn pdf_dir(&self, next: &Point3) -> f64 {
+        // Solid angle density of leaving this vertex towards next.
+        let direction = *next - self.p;
+        match self.kind {
+            VertexKind::Light => direction.unit().dot(self.normal).abs() / PI,
+            VertexKind::Surface | VertexKind::Medium => self.pdf.as_ref().map_or(0.0, |pdf| pdf.value(&direction)),
+            VertexKind::Camera => 0.0,
+        }
+    }
+
+    fn convert_density(&self, pdf_dir: f64, next: &Vertex) -> f64 {
+        // Turns a solid angle density at this vertex into an area density at next.
+        let w = next.p - self.p;
+        let distance_squared = w.squared_length();
+        if distance_squared <= 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf_dir / distance_squared;
+        if next.kind == VertexKind::Surface || next.kind == VertexKind::Light {
+            pdf *= next.normal.dot(w.unit()).abs();
+        }
+        pdf
+    }
+
+    fn pdf_area(&self, next: &Vertex) -> f64 {
+        self.convert_density(self.pdf_dir(&next.p), next)
+    }
+
+    fn emission_pdf_area(&self, next: &Vertex) -> f64 {
+        // Area density at next of this emitting vertex sending light its way.
+        let direction = (next.p - self.p).unit();
+        self.convert_density(direction.dot(self.normal).abs() / PI, next)
+    }
+
+    fn f(&self, next: &Point3, materials: &Materials) -> Color {
+        // Light carried from next through this vertex, times the cosine at this vertex.
+        let direction = *next - self.p;
+        match self.kind {
+            VertexKind::Light => {
+                let cosine = direction.unit().dot(self.normal);
+                if cosine <= 0.0 {
+                    return Color::zero();
+                }
+                let mat = materials.get(self.rec.mat.unwrap());
+                mat.emitted(&self.r_in, &self.rec, self.rec.u, self.rec.v, &self.p) * cosine
+            }
+            VertexKind::Surface | VertexKind::Medium => {
+                // Directions the material never scatters into, such as below a diffuse surface, carry nothing.
+                if self.pdf_dir(next) <= 0.0 {
+                    return Color::zero();
+                }
+                let mat = materials.get(self.rec.mat.unwrap());
+                let scattered = Ray::new(self.p, direction, self.r_in.time());
+                self.attenuation * mat.scattering_pdf(&self.r_in, &self.rec, &scattered)
+            }
+            VertexKind::Camera => Color::zero(),
+        }
+    }
+}
+
+fn is_black(c: Color) -> bool {
+    c.x <= 0.0 && c.y <= 0.0 && c.z <= 0.0
+}
+
+fn remap0(pdf: f64) -> f64 {
+    if pdf != 0.0 { pdf } else { 1.0 }
+}
+
+// Bidirectional path tracer: traces a path from the camera and one from a light, and connects every
+// pair of their vertices, weighting each way of building a path by the balance heuristic. Paths that
+// end on the camera from the light side (a light path vertex seen directly by the camera) are not
+// traced, so every path has at least one camera vertex past the lens.
+pub struct Bdpt {
+    emission: EmissionSampler, // Where light paths start
+    materials: Materials,
+}
+
+impl Bdpt {
+    pub fn new(emitters: &HittableList, materials: &Materials) -> Self {
+        Bdpt { emission: EmissionSampler::new(emitters, materials), materials: materials.clone() }
+    }
+
+    pub fn radiance(&self, r: &Ray, background: Color, max_depth: u32, world: &HittableList, delta_lights: &[Arc<dyn Light + Send + Sync>]) -> Color {
+        // Same bounce limit as ray_color: paths of at most max_depth surface vertices.
+        let max_depth = max_depth as usize;
+        if max_depth == 0 {
+            return Color::zero();
+        }
+
+        let mut camera_path = vec![Vertex::camera(r)];
+        // The camera's own density only matters to the strategy that isn't traced, so any value will do.
+        let escaped = self.walk(*r, Color::new(1.0, 1.0, 1.0), 1.0, max_depth + 1, world, &mut camera_path);
+        let light_path = self.light_path(world, max_depth, r.time());
+
+        // Only the camera path can find the background, so it is counted in full.
+        let mut color = match escaped {
+            Some(beta) => beta * background,
+            None => Color::zero(),
+        };
+
+        for t in 2..=camera_path.len() {
+            for s in 0..=light_path.len().min(max_depth + 1 - t) {
+                let contribution = self.connect(&light_path, &camera_path, s, t, world);
+                if !is_black(contribution) {
+                    color += contribution * self.mis_weight(&light_path, &camera_path, s, t);
+                }
+            }
+        }
+
+        // Delta lights can only be reached by a shadow ray, so that one strategy gets the full weight.
+        for vertex in &camera_path[1..] {
+            if let (Some(pdf), false) = (vertex.pdf, vertex.delta) {
+                let srec = ScatterRecord { attenuation: vertex.attenuation, pdf, ..ScatterRecord::default() };
+                color += vertex.beta * vertex.r_in.delta_light_color(&vertex.rec, &srec, world, &self.materials, delta_lights);
+            }
+        }
+
+        color
+    }
+
+    fn light_path(&self, world: &HittableList, max_vertices: usize, time: f64) -> Vec<Vertex> {
+        let mut path = Vec::new();
+        let sample = match self.emission.sample(time) {
+            Some(sample) => sample,
+            None => return path,
+        };
+
+        let origin = Vertex {
+            kind: VertexKind::Light,
+            p: sample.p(),
+            normal: sample.normal,
+            rec: sample.rec.clone(),
+            r_in: sample.probe,
+            attenuation: Color::zero(),
+            pdf: None,
+            beta: Color::new(1.0, 1.0, 1.0) / sample.pdf_pos,
+            delta: false,
+            pdf_fwd: sample.pdf_pos,
+            pdf_rev: 0.0,
+            emitted: Color::zero(),
+        };
+        let beta = origin.beta * origin.f(&(sample.p() + sample.direction), &self.materials) / sample.pdf_dir;
+
+        path.push(origin);
+        self.walk(Ray::new(sample.p(), sample.direction, time), beta, sample.pdf_dir, max_vertices, world, &mut path);
+        path
+    }
+
+    fn walk(&self, mut r: Ray, mut beta: Color, mut pdf_dir: f64, max_vertices: usize, world: &HittableList, path: &mut Vec<Vertex>) -> Option<Color> {
+        // Extends path by scattering until it has max_vertices vertices or is absorbed. Returns the
+        // throughput if the last ray escapes the scene.
+        while path.len() < max_vertices {
+            // The first ray of a camera path is the primary ray, counted by the camera.
+            if path.len() > 1 || path[0].kind != VertexKind::Camera {
+                count(Counter::SecondaryRay);
+            }
+            let mut rec = HitRecord::default();
+            if !world.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+                return Some(beta);
+            }
+            rec.set_footprint(&r);
+            let geometric_normal = rec.normal;
+            rec.apply_shading_normal();
+
+            let mat = self.materials.get(rec.mat.unwrap());
+            let in_medium = mat.as_any().is::<Isotropic>();
+            let prev = path.len() - 1;
+
+            let mut vertex = Vertex {
+                kind: if in_medium { VertexKind::Medium } else { VertexKind::Surface },
+                p: rec.p,
+                normal: if in_medium { Vec3::zero() } else { geometric_normal },
+                emitted: mat.emitted(&r, &rec, rec.u, rec.v, &rec.p),
+                rec: HitRecord::default(),
+                r_in: r,
+                attenuation: Color::zero(),
+                pdf: None,
+                beta,
+                delta: false,
+                pdf_fwd: 0.0,
+                pdf_rev: 0.0,
+            };
+            vertex.pdf_fwd = path[prev].convert_density(pdf_dir, &vertex);
+
+            let mut srec = ScatterRecord::default();
+            if !mat.scatter(&r, &rec, &mut srec) {
+                vertex.rec = rec;
+                path.push(vertex);
+                return None;
+            }
+            vertex.attenuation = srec.attenuation;
+
+            if srec.skip_pdf {
+                // Specular: the densities are left at zero and the balance heuristic skips this vertex.
+                vertex.delta = true;
+                vertex.rec = rec;
+                path.push(vertex);
+                beta = beta * srec.attenuation;
+                pdf_dir = 0.0;
+                r = srec.skip_pdf_ray;
+                continue;
+            }
+
+            let scattered = Ray::new(rec.p, srec.pdf.generate(), r.time());
+            pdf_dir = srec.pdf.value(scattered.direction());
+            let f = srec.attenuation * mat.scattering_pdf(&r, &rec, &scattered);
+            let pdf_rev_dir = srec.pdf.value(&(-1.0 * *r.direction()));
+
+            vertex.rec = rec;
+            vertex.pdf = Some(srec.pdf);
+            path.push(vertex);
+            path[prev].pdf_rev = path[prev + 1].convert_density(pdf_rev_dir, &path[prev]);
+
+            if pdf_dir <= 0.0 {
+                return None;
+            }
+            beta = beta * f / pdf_dir;
+            r = scattered;
+        }
+        None
+    }
+
+    fn connect(&self, light_path: &[Vertex], camera_path: &[Vertex], s: usize, t: usize, world: &HittableList) -> Color {
+        // Unweighted contribution of the path made of the first s light and first t camera vertices.
+        let pt = &camera_path[t - 1];
+        if s == 0 {
+            return pt.beta * pt.emitted;
+        }
+
+        let qs = &light_path[s - 1];
+        if !pt.is_connectible() || !qs.is_connectible() {
+            return Color::zero();
+        }
+
+        let to_light = qs.p - pt.p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return Color::zero();
+        }
+
+        let contribution = qs.beta * qs.f(&pt.p, &self.materials) * pt.f(&qs.p, &self.materials) * pt.beta / (distance * distance);
+        if is_black(contribution) {
+            return Color::zero();
+        }
+
+        let shadow_ray = Ray::new(pt.p, to_light / distance, pt.r_in.time());
+        let mut shadow_rec = HitRecord::default();
+        count(Counter::ShadowRay);
+        if world.hit(&shadow_ray, Interval::with_values(0.001, distance - 0.001), &mut shadow_rec) {
+            return Color::zero();
+        }
+        contribution
+    }
+
+    fn mis_weight(&self, light_path: &[Vertex], camera_path: &[Vertex], s: usize, t: usize) -> f64 {
+        // Balance heuristic over every traced way of sampling the same path, following the ratios of
+        // densities vertex by vertex from the connection outwards.
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        // (pdf_fwd, pdf_rev, delta) of each vertex, with the densities the connection changes.
+        let mut camera: Vec<(f64, f64, bool)> = camera_path[..t].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+        let mut light: Vec<(f64, f64, bool)> = light_path[..s].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+
+        let pt = &camera_path[t - 1];
+        let pt_minus = &camera_path[t - 2];
+        if s > 0 {
+            let qs = &light_path[s - 1];
+            camera[t - 1].1 = qs.pdf_area(pt);
+            camera[t - 2].1 = pt.pdf_area(pt_minus);
+            light[s - 1].1 = pt.pdf_area(qs);
+            if s > 1 {
+                light[s - 2].1 = qs.pdf_area(&light_path[s - 2]);
+            }
+        } else {
+            camera[t - 1].1 = self.emission.origin_pdf(&pt.rec);
+            camera[t - 2].1 = pt.emission_pdf_area(pt_minus);
+        }
+        camera[t - 1].2 = false;
+        if s > 0 {
+            light[s - 1].2 = false;
+        }
+
+        let mut sum_ri = 0.0;
+        let mut ri = 1.0;
+        // Moving the connection towards the camera; a single camera vertex isn't a traced strategy.
+        for i in (2..t).rev() {
+            ri *= remap0(camera[i].1) / remap0(camera[i].0);
+            if !camera[i].2 && !camera[i - 1].2 {
+                sum_ri += ri;
+            }
+        }
+
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap0(light[i].1) / remap0(light[i].0);
+            let delta_before = i > 0 && light[i - 1].2;
+            if !light[i].2 && !delta_before {
+                sum_ri += ri;
+            }
+        }
+
+        1.0 / (1.0 + sum_ri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light_sampler::{LightSampler, LightSampling};
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::quad::Quad;
+    use crate::rtweekend::seed_random;
+    use crate::sphere::Sphere;
+
+    fn lit_floor() -> (HittableList, Materials) {
+        // A grey floor under a square light, facing down.
+        let mut materials = Materials::new();
+        let grey = Some(materials.add(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let light = Some(materials.add(DiffuseLight::with_color(Color::new(4.0, 4.0, 4.0))));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(Point3::new(-5.0, 0.0, -5.0), Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 10.0), grey)));
+        world.add(Arc::new(Quad::new(Point3::new(-1.0, 2.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0), light)));
+        (world, materials)
+    }
+
+    #[test]
+    fn test_light_path_starts_on_light() {
+        let (world, materials) = lit_floor();
+        let mut emitters = HittableList::new();
+        world.collect_emitters(&materials, &mut emitters);
+        let bdpt = Bdpt::new(&emitters, &materials);
+
+        for _ in 0..50 {
+            let path = bdpt.light_path(&world, 3, 0.0);
+            let origin = &path[0];
+            assert!(origin.kind == VertexKind::Light);
+            assert!((origin.p.y - 2.0).abs() < 1e-9);
+            assert!((origin.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-9);
+            // Uniform by area over a 2x2 light that carries all the power.
+            assert!((origin.pdf_fwd - 0.25).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_sampled_sphere_points_recover_hits() {
+        let mut materials = Materials::new();
+        let light = Some(materials.add(DiffuseLight::with_color(Color::new(1.0, 1.0, 1.0))));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, light)));
+        let bdpt = Bdpt::new(&world, &materials);
+        for _ in 0..50 {
+            let path = bdpt.light_path(&world, 1, 0.0);
+            assert_eq!(path.len(), 1);
+            assert!(((path[0].p - Point3::new(1.0, 2.0, 3.0)).length() - 0.5).abs() < 1e-9);
+            assert!(path[0].rec.front_face);
+        }
+    }
+
+    #[test]
+    fn test_matches_path_tracer() {
+        // Both integrators estimate the same light reflected off the floor. The path tracer samples
+        // the material only, which finds this large light often enough on its own.
+        seed_random(361);
+        let (world, materials) = lit_floor();
+        let mut emitters = HittableList::new();
+        world.collect_emitters(&materials, &mut emitters);
+        let bdpt = Bdpt::new(&emitters, &materials);
+        let lights = LightSampler::new(HittableList::new(), &materials, LightSampling::Power);
+
+        let r = Ray::new(Point3::new(0.0, 1.0, 4.0), Vec3::new(0.0, -1.0, -2.0), 0.0);
+        let n = 100000;
+        let mut bidirectional = Color::zero();
+        let mut unidirectional = Color::zero();
+        for _ in 0..n {
+            bidirectional += bdpt.radiance(&r, Color::zero(), 4, &world, &[]);
+            unidirectional += r.ray_color(Color::zero(), 4, &world, &materials, &lights, &[]);
+        }
+        let bidirectional = bidirectional.x / n as f64;
+        let unidirectional = unidirectional.x / n as f64;
+        assert!(unidirectional > 0.0);
+        assert!((bidirectional - unidirectional).abs() < 0.05 * unidirectional, "{} vs {}", bidirectional, unidirectional);
+    }
+}
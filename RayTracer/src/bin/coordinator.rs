@@ -0,0 +1,65 @@
+use indicatif::ProgressBar;
+use raytracer::distributed::Coordinator;
+use raytracer::scenes;
+use std::process::exit;
+use std::time::Duration;
+
+// Hands out the tiles of a scene to workers started with the worker command, and writes the image
+// once they are all in:
+//
+//     coordinator [--tile-timeout <seconds>] <address> <output> <scene description>
+//
+// for example `coordinator 0.0.0.0:7878 output/final.jpg final_scene 800 800 10000 40`. An output
+// ending in .acc is written as a raw accumulation, for merging with other renders of the scene. A worker that
+// stays silent for the tile timeout, a minute unless given, loses its tile to another.
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let tile_timeout = args.iter().position(|arg| arg == "--tile-timeout").map(|k| {
+        let seconds = args.get(k + 1).and_then(|arg| arg.parse::<f64>().ok()).filter(|seconds| *seconds > 0.0);
+        let Some(seconds) = seconds else {
+            eprintln!("--tile-timeout needs a number of seconds");
+            exit(2);
+        };
+        args.drain(k..k + 2);
+        Duration::from_secs_f64(seconds)
+    });
+    if args.len() < 3 {
+        eprintln!("usage: coordinator [--tile-timeout <seconds>] <address> <output> <scene description>");
+        exit(2);
+    }
+    let (address, output, description) = (&args[0], &args[1], args[2..].join(" "));
+
+    let mut coordinator = match Coordinator::bind(address.as_str(), &description, scenes::from_description) {
+        Ok(coordinator) => coordinator.with_seed(scenes::SCENE_SEED),
+        Err(e) => {
+            eprintln!("Starting the coordinator fails: {}", e);
+            exit(1);
+        }
+    };
+    if let Some(tile_timeout) = tile_timeout {
+        coordinator = coordinator.with_tile_timeout(tile_timeout);
+    }
+    if let Ok(addr) = coordinator.local_addr() {
+        println!("Waiting for workers on {}", addr);
+    }
+
+    let bar = ProgressBar::new(0);
+    let image = coordinator.run(|done, total| {
+        bar.set_length(total as u64);
+        bar.set_position(done as u64);
+    });
+    bar.finish_with_message("Rendering complete");
+
+    let result = image.map_err(|e| e.to_string()).and_then(|image| {
+        if output.ends_with(".acc") {
+            image.save(output).map_err(|e| e.to_string())
+        } else {
+            image.to_framebuffer().to_rgb_image().save(output).map_err(|e| e.to_string())
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Rendering \"{}\" fails: {}", output, e);
+        exit(1);
+    }
+    println!("Ouput image as \"{}\"", output);
+}
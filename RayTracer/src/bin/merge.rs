@@ -0,0 +1,48 @@
+use raytracer::accumulation::Accumulation;
+use std::process::exit;
+
+// Combines raw accumulations from renders of the same scene, run apart with their own random
+// samples, into one image holding all their samples:
+//
+//     merge <output> <input.acc>...
+//
+// An output ending in .acc is written as another raw accumulation, anything else as an image in
+// the format its extension names. Inputs that are of different scenes, or of a scene built with
+// other random numbers, are refused.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("usage: merge <output> <input.acc>...");
+        exit(2);
+    }
+    let (output, inputs) = (&args[0], &args[1..]);
+
+    let mut merged: Option<Accumulation> = None;
+    for input in inputs {
+        let accumulation = Accumulation::load(input).unwrap_or_else(|e| {
+            eprintln!("Reading \"{}\" fails: {}", input, e);
+            exit(1);
+        });
+        match &mut merged {
+            None => merged = Some(accumulation),
+            Some(merged) => {
+                if let Err(e) = merged.merge(&accumulation) {
+                    eprintln!("Merging \"{}\" fails: {}", input, e);
+                    exit(1);
+                }
+            }
+        }
+    }
+    let merged = merged.unwrap();
+
+    let result = if output.ends_with(".acc") {
+        merged.save(output).map_err(|e| e.to_string())
+    } else {
+        merged.to_framebuffer().to_rgb_image().save(output).map_err(|e| e.to_string())
+    };
+    if let Err(e) = result {
+        eprintln!("Writing \"{}\" fails: {}", output, e);
+        exit(1);
+    }
+    println!("Merged {} renders into \"{}\"", inputs.len(), output);
+}
@@ -0,0 +1,22 @@
+use raytracer::distributed::run_worker;
+use raytracer::scenes;
+use std::process::exit;
+
+// Renders tiles for a coordinator until its image is done:
+//
+//     worker <coordinator address>
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("usage: worker <coordinator address>");
+        exit(2);
+    }
+
+    match run_worker(args[0].as_str(), scenes::from_description) {
+        Ok(rendered) => println!("Rendered {} tiles", rendered),
+        Err(e) => {
+            eprintln!("Working for \"{}\" fails: {}", args[0], e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::hit::{HitRecord, Hittable, HittableClone};
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+use crate::aabb::AABB;
+use crate::hit_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Materials;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use std::sync::Arc;
+
+// Step in uv used to difference a bump texture when the hit has no pixel footprint.
+const BUMP_DELTA: f64 = 0.0005;
+
+pub trait ShadingNormal {
+    // Unit shading normal at the hit, on the same side of the surface as rec.normal.
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3;
+}
+
+fn luminance(c: Vec3) -> f64 {
+    (c.x + c.y + c.z) / 3.0
+}
+
+fn same_side(n: Vec3, geometric: Vec3) -> Vec3 {
+    if n.near_zero() {
+        return geometric;
+    }
+    let n = n.unit();
+    if n.dot(geometric) < 0.0 { -1.0 * n } else { n }
+}
+
+#[derive(Clone)]
+pub struct NormalMap {
+    tex: Arc<dyn Texture + Send + Sync>, // Tangent-space normals encoded as rgb = 0.5 * n + 0.5
+    strength: f64, // Scales the tangent-plane tilt, 1 uses the map as is
+}
+
+impl NormalMap {
+    pub fn new(tex: Arc<dyn Texture + Send + Sync>) -> Self {
+        NormalMap { tex, strength: 1.0 }
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+}
+
+impl ShadingNormal for NormalMap {
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let c = self.tex.value_filtered(rec);
+        let (tangent, bitangent) = rec.tangent_frame();
+        let x = (2.0 * c.x - 1.0) * self.strength;
+        let y = (2.0 * c.y - 1.0) * self.strength;
+        let z = (2.0 * c.z - 1.0).max(0.0);
+        same_side(x * tangent + y * bitangent + z * rec.normal, rec.normal)
+    }
+}
+
+#[derive(Clone)]
+pub struct BumpMap {
+    height: Arc<dyn Texture + Send + Sync>, // Height is the mean of the texture's rgb
+    scale: f64, // Displacement in world units for a height of 1
+}
+
+impl BumpMap {
+    pub fn new(height: Arc<dyn Texture + Send + Sync>, scale: f64) -> Self {
+        BumpMap { height, scale }
+    }
+
+    fn height_at(&self, u: f64, v: f64, p: Point3) -> f64 {
+        self.scale * luminance(self.height.value(u, v, &p))
+    }
+}
+
+impl ShadingNormal for BumpMap {
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        // Surfaces without uv derivatives are bumped along an arbitrary unit tangent frame.
+        let (dpdu, dpdv) = if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+            rec.tangent_frame()
+        } else {
+            (rec.dpdu, rec.dpdv)
+        };
+
+        // Difference over about a pixel's footprint so that the bumps do not alias.
+        let fp = rec.footprint;
+        let du = 0.5 * (fp.major.0.abs() + fp.minor.0.abs());
+        let dv = 0.5 * (fp.major.1.abs() + fp.minor.1.abs());
+        let du = if du > 0.0 { du } else { BUMP_DELTA };
+        let dv = if dv > 0.0 { dv } else { BUMP_DELTA };
+
+        // Displaced surface p + h * n; the change of n itself across the surface is ignored.
+        let h = self.height_at(rec.u, rec.v, rec.p);
+        let h_u = self.height_at(rec.u + du, rec.v, rec.p + du * dpdu);
+        let h_v = self.height_at(rec.u, rec.v + dv, rec.p + dv * dpdv);
+
+        let bumped_dpdu = dpdu + ((h_u - h) / du) * rec.normal;
+        let bumped_dpdv = dpdv + ((h_v - h) / dv) * rec.normal;
+        same_side(bumped_dpdu.cross(bumped_dpdv), rec.normal)
+    }
+}
+
+// Gives the wrapped object a shading normal that is applied just before its material scatters.
+#[derive(Clone)]
+pub struct Bumped {
+    object: Arc<dyn Hittable + Send + Sync>,
+    shading: Arc<dyn ShadingNormal + Send + Sync>,
+}
+
+impl Bumped {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, shading: Arc<dyn ShadingNormal + Send + Sync>) -> Self {
+        Bumped { object, shading }
+    }
+}
+
+impl Hittable for Bumped {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if !self.object.hit(r, ray_t, rec) {
+            return false;
+        }
+        rec.shading = Some(self.shading.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(origin)
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        self.object.collect_emitters(materials, lights);
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        self.object.emitted_power(materials)
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        self.object.sample_surface()
+    }
+}
+
+impl HittableClone for Bumped {
+    fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad::Quad;
+    use crate::texture::{NoiseTexture, SolidColor};
+
+    fn floor_hit(shading: Arc<dyn ShadingNormal + Send + Sync>) -> HitRecord {
+        // Unit quad in the xy-plane facing +z, hit head on.
+        let quad = Quad::new(Point3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), None);
+        let object = Bumped::new(Arc::new(quad), shading);
+        let r = Ray::new(Point3::new(0.2, 0.3, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::default();
+        assert!(object.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+        rec.apply_shading_normal();
+        rec
+    }
+
+    #[test]
+    fn test_flat_normal_map_keeps_normal() {
+        let flat = Arc::new(SolidColor::solid_color(0.5, 0.5, 1.0));
+        let rec = floor_hit(Arc::new(NormalMap::new(flat)));
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_normal_map_tilts_along_tangent() {
+        // Pure +x in tangent space tilts the normal towards dpdu.
+        let tilted = Arc::new(SolidColor::solid_color(1.0, 0.5, 0.5));
+        let rec = floor_hit(Arc::new(NormalMap::new(tilted)));
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_noise_bump_perturbs_unit_normal() {
+        let bump = BumpMap::new(Arc::new(NoiseTexture::with_scale(4.0)), 0.05);
+        let rec = floor_hit(Arc::new(bump));
+        assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+        assert!(rec.normal.z > 0.0);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() > 1e-6);
+    }
+}
@@ -2,21 +2,47 @@ use crate::hit::{Hittable, HitRecord, HittableClone};
 use crate::hit_list::HittableList;
 use crate::aabb::AABB;
 use crate::interval::Interval;
+use crate::material::Materials;
+use crate::primitive::Primitive;
 use crate::ray::Ray;
+use crate::stats::{count, Counter};
 use std::cmp::Ordering;
 use std::sync::Arc;
 
+// Children are matched on rather than called through a vtable, down to the shapes at the leaves.
+#[derive(Clone)]
+enum BvhChild {
+    Node(Arc<BVHNode>),
+    Leaf(Primitive),
+}
+
+impl BvhChild {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        match self {
+            BvhChild::Node(node) => node.hit(r, ray_t, rec),
+            BvhChild::Leaf(primitive) => primitive.hit(r, ray_t, rec),
+        }
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        match self {
+            BvhChild::Node(node) => node.collect_emitters(materials, lights),
+            BvhChild::Leaf(primitive) => primitive.collect_emitters(materials, lights),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BVHNode {
-    left: Arc<dyn Hittable + Send + Sync>,
-    right: Arc<dyn Hittable + Send + Sync>,
+    left: BvhChild,
+    right: Option<BvhChild>, // None when the node holds a single object
     bbox: AABB,
 }
 
 impl BVHNode {
     fn box_compare(a: &Arc<dyn Hittable + Send + Sync>, b: &Arc<dyn Hittable + Send + Sync>, axis_index: usize) -> Ordering {
-        let a_axis_interval = a.bounding_box().axis_interval(axis_index).clone();
-        let b_axis_interval = b.bounding_box().axis_interval(axis_index).clone();
+        let a_axis_interval = *a.bounding_box().axis_interval(axis_index);
+        let b_axis_interval = *b.bounding_box().axis_interval(axis_index);
         a_axis_interval.min.partial_cmp(&b_axis_interval.min).unwrap_or(Ordering::Equal)
     }
 
@@ -40,8 +66,8 @@ impl BVHNode {
     pub fn new_recursive(objects: &mut Vec<Arc<dyn Hittable + Send + Sync>>, start: usize, end: usize) -> Self {
         // Build the bounding box of the span of source objects.
         let mut bbox = AABB::new();
-        for object_index in start..end {
-            bbox = AABB::from_aabbs(&bbox, &objects[object_index].bounding_box());
+        for object in &objects[start..end] {
+            bbox = AABB::from_aabbs(&bbox, &object.bounding_box());
         }
 
         let axis = bbox.longest_axis();
@@ -55,20 +81,20 @@ impl BVHNode {
     
         let object_span = end - start;
     
-        let (left, right): (Arc<dyn Hittable + Send + Sync>, Arc<dyn Hittable + Send + Sync>);
+        let (left, right);
     
         if object_span == 1 {
-            left = objects[start].clone();
-            right = objects[start].clone();
+            left = BvhChild::Leaf(Primitive::new(&objects[start]));
+            right = None;
         } else if object_span == 2 {
-            left = objects[start].clone();
-            right = objects[start + 1].clone();
+            left = BvhChild::Leaf(Primitive::new(&objects[start]));
+            right = Some(BvhChild::Leaf(Primitive::new(&objects[start + 1])));
         } else {
-            objects[start..end].sort_by(|a, b| comparator(a, b));
+            objects[start..end].sort_by(comparator);
     
             let mid = start + object_span / 2;
-            left = Arc::new(BVHNode::new_recursive(objects, start, mid));
-            right = Arc::new(BVHNode::new_recursive(objects, mid, end));
+            left = BvhChild::Node(Arc::new(BVHNode::new_recursive(objects, start, mid)));
+            right = Some(BvhChild::Node(Arc::new(BVHNode::new_recursive(objects, mid, end))));
         }
     
         BVHNode {
@@ -81,13 +107,14 @@ impl BVHNode {
 
 impl Hittable for BVHNode {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        count(Counter::BvhNode);
         if !self.bbox.hit(r, ray_t) {
             return false;
         }
 
         let hit_left = self.left.hit(r, ray_t, rec);
         let t = if hit_left { rec.t } else { ray_t.max };
-        let hit_right = self.right.hit(r, Interval::with_values(ray_t.min, t), rec);
+        let hit_right = self.right.as_ref().is_some_and(|right| right.hit(r, Interval::with_values(ray_t.min, t), rec));
 
         hit_left || hit_right
     }
@@ -95,6 +122,13 @@ impl Hittable for BVHNode {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        self.left.collect_emitters(materials, lights);
+        if let Some(right) = &self.right {
+            right.collect_emitters(materials, lights);
+        }
+    }
 }
 
 impl HittableClone for BVHNode {
@@ -2,19 +2,32 @@ use crate::ray::Ray;
 use crate::vec3::Vec3;
 type Point3 = Vec3;
 use crate::hit_list::HittableList;
-use crate::color::write_color;
-use indicatif::ProgressBar;
-use image::{ImageBuffer, RgbImage};
-use std::fs::File;
-use crate::rtweekend::random_double;
+use crate::light::Light;
+use crate::material::Materials;
+use crate::light_sampler::{LightSampler, LightSampling};
+use crate::bdpt::Bdpt;
+use crate::photon_map::PhotonMap;
+use crate::hit::Hittable;
+use crate::framebuffer::FrameBuffer;
+use crate::accumulation::Accumulation;
+use crate::scheduler::{tiles, Tile, TileOrder};
+use crate::rtweekend::{random_double, seed_random};
+use crate::stats::{self, Counter, RenderStats};
 type Color = Vec3;
-use std::sync::{Arc, Mutex, Condvar};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use crossbeam::thread;
 
-const HEIGHT_PARTITION: u32 = 20;
-const WIDTH_PARTITION: u32 = 20;
-const THREAD_LIMIT: usize = 40;
+// Algorithm used to estimate the light along each camera ray.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Integrator {
+    PathTracing, // Paths from the camera only, sampling lights at each bounce
+    Bidirectional, // Paths from both the camera and the lights, for light that arrives through caustics
+    // Path tracing with caustics estimated from photons shot from the lights, gathering up to
+    // nearest photons no farther than radius for each estimate.
+    PhotonMapping { photons: usize, nearest: usize, radius: f64 },
+}
 
 #[derive(Clone, Copy)]
 pub struct Camera {
@@ -25,7 +38,6 @@ pub struct Camera {
     pixel_delta_u: Vec3, // Offset to pixel to the right
     pixel_delta_v: Vec3, // Offset to pixel below
     pub samples_per_pixel: u32,
-    pixel_samples_scale: f64,
     pub max_depth: u32,
     pub vfov: f64, // Vertical view angle (field of view)
     pub lookfrom: Point3, // Point camera is looking from
@@ -41,12 +53,106 @@ pub struct Camera {
     pub background: Color,
     sqrt_spp: u32,
     recip_sqrt_spp: f64,
+    pixel_spread: f64, // Angle subtended by one pixel
+    pub light_sampling: LightSampling, // How a light is picked for each light sample
+    pub integrator: Integrator,
+    pub threads: usize, // Render threads, 0 for one per core
+    pub tile_size: u32, // Width and height of the square tiles handed to the threads
+    pub tile_order: TileOrder,
+    pub track_variance: bool, // Keep the per pixel sum of squares in raw accumulations
+    pub passes: u32, // Progressive passes the samples are split over, to show the image early
+    pub report_stats: bool, // Print the render statistics at the end of render
+    pub seed: Option<u64>, // Fixed random numbers for each tile, so renders come out the same every time
+}
+
+// Everything the render threads share, set up once per render.
+struct RenderState<'a> {
+    world: &'a HittableList,
+    materials: &'a Materials,
+    lights: LightSampler,
+    bdpt: Bdpt,
+    caustics: PhotonMap,
+    delta_lights: &'a [Arc<dyn Light + Send + Sync>],
 }
 
-const AUTHOR: &str = "name";
+// A camera ready to render one world, a region of the image at a time.
+pub struct Renderer<'a> {
+    camera: Camera,
+    state: RenderState<'a>,
+    pool: rayon::ThreadPool,
+    stats: Mutex<RenderStats>, // Over everything rendered so far
+}
+
+impl Renderer<'_> {
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    pub fn render_region(&self, region: &Tile, progress: &(dyn Fn(usize, usize) + Sync)) -> Accumulation {
+        self.render_passes(region, progress, &mut |_, _| {})
+    }
+
+    // Renders the pixels of region in the camera's number of passes, each adding a share of every
+    // pixel's samples over the whole region, and calls on_pass with the image so far and the
+    // number of passes done. The accumulation covers just the region.
+    pub fn render_passes(&self, region: &Tile, progress: &(dyn Fn(usize, usize) + Sync), on_pass: &mut dyn FnMut(&Accumulation, u32)) -> Accumulation {
+        let camera = &self.camera;
+        let tiles: Vec<Tile> = tiles(region.width(), region.height(), camera.tile_size, camera.tile_order)
+            .into_iter()
+            .map(|tile| Tile { x0: region.x0 + tile.x0, x1: region.x0 + tile.x1, y0: region.y0 + tile.y0, y1: region.y0 + tile.y1 })
+            .collect();
+
+        let passes = camera.effective_passes();
+        let strata = camera.sqrt_spp * camera.sqrt_spp;
+        let state = &self.state;
+        let total = region.pixel_count() * passes as usize;
+        let done = AtomicUsize::new(0);
+        let done = &done;
+        let stats = &self.stats;
+        let started = Instant::now();
+
+        let mut image = Accumulation::new(region.width(), region.height(), camera.track_variance);
+        for pass in 0..passes {
+            // Each pass takes its own run of the strata, so together they still cover each one once.
+            let first = strata * pass / passes;
+            let last = strata * (pass + 1) / passes;
+
+            // Each tile renders into its own buffer, so the threads never wait on each other.
+            let buffers: Vec<OnceLock<Accumulation>> = tiles.iter().map(|_| OnceLock::new()).collect();
+            // FIFO spawning hands the tiles out in order; idle threads steal the next ones waiting.
+            self.pool.scope_fifo(|s| {
+                for (tile, buffer) in tiles.iter().zip(&buffers) {
+                    s.spawn_fifo(move |_| {
+                        // Counts left over from whatever the thread did before belong to no tile.
+                        stats::take();
+                        if let Some(seed) = camera.seed {
+                            // Seeded by position rather than by thread, whichever thread gets the tile.
+                            seed_random(seed ^ (u64::from(pass) << 48 | u64::from(tile.y0) << 24 | u64::from(tile.x0)));
+                        }
+                        let tile_started = Instant::now();
+                        let (samples, pixel_costs) = camera.render_tile(tile, first..last, state);
+                        stats.lock().unwrap().add_tile(tile, tile_started.elapsed(), stats::take(), &pixel_costs);
+                        let _ = buffer.set(samples);
+                        let pixels = done.fetch_add(tile.pixel_count(), Ordering::Relaxed) + tile.pixel_count();
+                        progress(pixels, total);
+                    });
+                }
+            });
 
-pub fn is_ci() -> bool {
-    option_env!("CI").unwrap_or_default() == "true"
+            for (tile, buffer) in tiles.iter().zip(buffers) {
+                if let Some(buffer) = buffer.into_inner() {
+                    image.add_at(tile.x0 - region.x0, tile.y0 - region.y0, &buffer);
+                }
+            }
+            on_pass(&image, pass + 1);
+        }
+        self.stats.lock().unwrap().elapsed += started.elapsed();
+        image
+    }
 }
 
 impl Camera {
@@ -59,7 +165,6 @@ impl Camera {
             pixel_delta_u: Vec3::new(0.0, 0.0, 0.0),
             pixel_delta_v: Vec3::new(0.0, 0.0, 0.0),
             samples_per_pixel: 10,
-            pixel_samples_scale: 1.0 / 10.0, 
             max_depth : 10,
             vfov: 90.0,
             lookfrom: Point3::new(0.0, 0.0, 0.0),
@@ -75,11 +180,21 @@ impl Camera {
             background: Color::new(0.0, 0.0, 0.0),
             sqrt_spp: 10,
             recip_sqrt_spp: 0.1,
+            pixel_spread: 0.0,
+            light_sampling: LightSampling::Power,
+            integrator: Integrator::PathTracing,
+            threads: 0,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            track_variance: false,
+            passes: 1,
+            report_stats: false,
+            seed: None,
         }
     }
 
     pub fn initialize(&mut self) {
-        let center = self.lookfrom.clone();
+        let center = self.lookfrom;
 
         // Determine viewport dimensions.
         let theta = self.vfov.to_radians();
@@ -101,7 +216,7 @@ impl Camera {
 
         // Calculate the location of the upper left pixel.
         let viewport_upper_left = center - (self.focus_dist * self.w) - (viewport_u / 2.0) - (viewport_v / 2.0);
-        let pixel00_loc = viewport_upper_left + (pixel_delta_u.clone() + pixel_delta_v.clone()) * 0.5;
+        let pixel00_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
 
         // Calculate the camera defocus disk basis vectors.
         let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
@@ -109,105 +224,111 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
 
         self.sqrt_spp = (self.samples_per_pixel as f64).sqrt() as u32;
-        self.pixel_samples_scale = 1.0 / (self.sqrt_spp * self.sqrt_spp) as f64;
         self.recip_sqrt_spp = 1.0 / self.sqrt_spp as f64;
 
+        self.pixel_spread = pixel_delta_u.length() / self.focus_dist;
+
         self.center = center;
         self.pixel00_loc = pixel00_loc;
         self.pixel_delta_u = pixel_delta_u;
         self.pixel_delta_v = pixel_delta_v;
     }
 
-    pub fn render(&mut self, world: &HittableList, lights: &HittableList) {
-        self.initialize();
+    // Emissive objects in the world are sampled as lights automatically; extra_targets adds more
+    // objects worth sampling directly, such as a glass sphere that focuses light. delta_lights are
+    // point, spot and directional lights. progress is called from the render threads with the
+    // number of pixels done and the total.
+    pub fn render(&mut self, world: &HittableList, materials: &Materials, extra_targets: &HittableList, delta_lights: &[Arc<dyn Light + Send + Sync>], progress: &(dyn Fn(usize, usize) + Sync)) -> FrameBuffer {
+        self.render_accumulation(world, materials, extra_targets, delta_lights, progress).to_framebuffer()
+    }
 
-        let path = "output/test.jpg";
-        let quality = 60;
+    // Like render, but keeps the raw samples so renders made apart can be merged later.
+    pub fn render_accumulation(&mut self, world: &HittableList, materials: &Materials, extra_targets: &HittableList, delta_lights: &[Arc<dyn Light + Send + Sync>], progress: &(dyn Fn(usize, usize) + Sync)) -> Accumulation {
+        let whole = Tile { x0: 0, x1: self.image_width, y0: 0, y1: self.image_height };
+        let renderer = self.renderer(world, materials, extra_targets, delta_lights);
+        let image = renderer.render_region(&whole, progress);
+        if self.report_stats {
+            println!("{}", renderer.stats());
+        }
+        image
+    }
 
-        let bar: ProgressBar = if is_ci() {
-            ProgressBar::hidden()
-        } else {
-            ProgressBar::new((self.image_height * self.image_width) as u64)
+    // Sets up everything a render of world needs once, such as the light sampler and photon map,
+    // for rendering parts of the image separately.
+    pub fn renderer<'a>(&mut self, world: &'a HittableList, materials: &'a Materials, extra_targets: &HittableList, delta_lights: &'a [Arc<dyn Light + Send + Sync>]) -> Renderer<'a> {
+        self.initialize();
+        if let Some(seed) = self.seed {
+            seed_random(seed);
+        }
+
+        let mut lights = HittableList::new();
+        world.collect_emitters(materials, &mut lights);
+        let bdpt = Bdpt::new(&lights, materials);
+        let caustics = match self.integrator {
+            Integrator::PhotonMapping { photons, nearest, radius } => PhotonMap::caustics(world, materials, &lights, photons, self.max_depth, nearest, radius),
+            _ => PhotonMap::new(Vec::new(), 0, 0.0),
+        };
+        lights.addlist(extra_targets.clone());
+        let state = RenderState {
+            world,
+            materials,
+            lights: LightSampler::new(lights, materials, self.light_sampling),
+            bdpt,
+            caustics,
+            delta_lights,
         };
 
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-
-        let chunk_height = (self.image_height + HEIGHT_PARTITION - 1) / HEIGHT_PARTITION;
-        let chunk_width = (self.image_width + WIDTH_PARTITION - 1) / WIDTH_PARTITION;
-
-        let mut img: RgbImage = ImageBuffer::new(self.image_width, self.image_height);
-        let img_mtx = Arc::new(Mutex::new(&mut img));
-
-        thread::scope(|s| {
-            let thread_count = Arc::new(AtomicUsize::new(0));
-            let thread_number_controller = Arc::new(Condvar::new());
-            let camera_wrapper = Arc::new(self);
-            let world_wrapper = Arc::new(&world);
-            for j in 0..HEIGHT_PARTITION {
-                for i in 0..WIDTH_PARTITION {
-                    let img_clone = Arc::clone(&img_mtx);
-                    let bar_clone = bar.clone();
-                    let thread_count_clone = Arc::clone(&thread_count);
-                    let thread_number_controller_clone = Arc::clone(&thread_number_controller);
-                    let cam_clone = Arc::clone(&camera_wrapper);
-        
-                    let lock_for_condv = Mutex::new(false);
-                    while !(thread_count.load(Ordering::SeqCst) < THREAD_LIMIT) { // outstanding thread number control
-                      thread_number_controller.wait(lock_for_condv.lock().unwrap()).unwrap();
-                    }
-        
-                    s.spawn(move |_| {
-                        cam_clone.render_sub(&world, &img_clone, &bar_clone, 
-                          i * chunk_width, (i + 1) * chunk_width, 
-                          j * chunk_height, (j + 1) * chunk_height, &lights);
-        
-                        thread_count_clone.fetch_sub(1, Ordering::SeqCst); // subtract first, then notify.
-                        bar_clone.set_message(format!("|{} threads outstanding|", thread_count_clone.load(Ordering::SeqCst)));
-                        // NOTIFY
-                        thread_number_controller_clone.notify_one();
-                    });
-                }
-            }
-        }).unwrap();
+        Renderer {
+            camera: *self,
+            state,
+            pool: rayon::ThreadPoolBuilder::new().num_threads(self.threads).build().unwrap(),
+            stats: Mutex::new(RenderStats::new(self.image_width, self.image_height)),
+        }
+    }
+
+    pub fn set_image_size(&mut self, image_height: u32, image_width: u32) {
+        self.image_height = image_height;
+        self.image_width = image_width;
+    }
 
-        bar.finish_with_message("Rendering complete");
+    pub fn image_width(&self) -> u32 {
+        self.image_width
+    }
 
-    
-        println!("Ouput image as \"{}\"\n Author: {}", path, AUTHOR);
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+    }
 
-        let output_image = image::DynamicImage::ImageRgb8(img);
-        let mut output_file: File = File::create(path).unwrap();
-        match output_image.write_to(&mut output_file, image::ImageOutputFormat::Jpeg(quality)) {
-            Ok(_) => {}
-            Err(_) => println!("Outputting image fails."),
-        }
+    // Passes a render really takes. Each needs a stratum of its own, and there are as many strata
+    // as the largest square number of samples per pixel.
+    pub fn effective_passes(&self) -> u32 {
+        let sqrt_spp = (self.samples_per_pixel as f64).sqrt() as u32;
+        self.passes.clamp(1, (sqrt_spp * sqrt_spp).max(1))
     }
 
-    pub fn render_sub(&self, world: &HittableList, img_mtx: &Mutex<&mut RgbImage>, bar: &ProgressBar, x_min: u32, x_max: u32, y_min: u32, y_max: u32, lights: &HittableList) {
-        let x_max = x_max.min(self.image_width);
-        let y_max = y_max.min(self.image_height);
-        
-        let mut temp_buf: Vec<(usize, usize, Vec3)> = Vec::new();
-
-        for j in y_min..y_max {
-            for i in x_min..x_max {
-                let mut pixel_color = Vec3::new(0.0, 0.0, 0.0);
-                for s_j in 0..self.sqrt_spp {
-                    for s_i in 0..self.sqrt_spp {
-                        let r = self.get_ray(i, j, s_i, s_j);
-                        pixel_color += r.ray_color(self.background, self.max_depth, world, lights);
-                    }
+    fn render_tile(&self, tile: &Tile, strata: Range<u32>, state: &RenderState) -> (Accumulation, Vec<f64>) {
+        // Samples of the tile's pixels in the given strata, numbered row by row, in an
+        // accumulation the size of the tile, along with the seconds spent on each pixel.
+        let mut buffer = Accumulation::new(tile.width(), tile.height(), self.track_variance);
+        let mut pixel_costs = Vec::with_capacity(tile.pixel_count());
+        for j in tile.y0..tile.y1 {
+            for i in tile.x0..tile.x1 {
+                let pixel_started = Instant::now();
+                for stratum in strata.clone() {
+                    let (s_i, s_j) = (stratum % self.sqrt_spp, stratum / self.sqrt_spp);
+                    let r = self.get_ray(i, j, s_i, s_j);
+                    stats::count(Counter::PrimaryRay);
+                    let sample = match self.integrator {
+                        Integrator::PathTracing => r.ray_color(self.background, self.max_depth, state.world, state.materials, &state.lights, state.delta_lights),
+                        Integrator::Bidirectional => state.bdpt.radiance(&r, self.background, self.max_depth, state.world, state.delta_lights),
+                        Integrator::PhotonMapping { .. } => r.ray_color_with_caustics(self.background, self.max_depth, state.world, state.materials, &state.lights, state.delta_lights, &state.caustics),
+                    };
+                    buffer.add_sample(i - tile.x0, j - tile.y0, sample);
                 }
-                pixel_color = pixel_color * self.pixel_samples_scale;
-                temp_buf.push((i as usize, j as usize, pixel_color));
-                bar.inc(1);
+                pixel_costs.push(pixel_started.elapsed().as_secs_f64());
             }
         }
-        
-        let mut img = img_mtx.lock().unwrap();
-        for (i, j, color) in temp_buf {
-            write_color(color, &mut img, i, j)
-        }
+        (buffer, pixel_costs)
     }
 
     fn get_ray(&self, i: u32, j: u32, s_i: u32, s_j: u32) -> Ray {
@@ -223,7 +344,8 @@ impl Camera {
         let ray_direction = pixel_sample - ray_origin;
         let ray_time = random_double(0.0, 1.0);
 
-        Ray::new(ray_origin, ray_direction, ray_time)
+        // Each camera ray stands for a cone about one pixel wide, used for texture filtering.
+        Ray::new(ray_origin, ray_direction, ray_time).with_spread(self.pixel_spread)
     }
 
     fn sample_square_stratified(&self, s_i: u32, s_j: u32) -> Vec3 {
@@ -241,9 +363,94 @@ impl Camera {
         let p = Vec3::random_in_unit_disk();
         self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::glowing_wall;
+
+    #[test]
+    fn test_tiled_render_fills_every_pixel() {
+        // A light filling the view, seen through tiles that don't divide the image evenly.
+        let (world, materials) = glowing_wall();
+
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let mut cam = Camera::new(7, 11);
+            cam.samples_per_pixel = 1;
+            cam.threads = 3;
+            cam.tile_size = 4;
+            cam.tile_order = order;
+
+            let last = AtomicUsize::new(0);
+            let image = cam.render(&world, &materials, &HittableList::new(), &[], &|done, total| {
+                assert_eq!(total, 7 * 11);
+                last.fetch_max(done, Ordering::Relaxed);
+            });
+            assert_eq!(last.load(Ordering::Relaxed), 7 * 11);
+            assert_eq!((image.width(), image.height()), (11, 7));
+            assert!(image.pixels().iter().all(|p| (p.x - 0.5).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_accumulation_counts_every_sample() {
+        let (world, materials) = glowing_wall();
+
+        let mut cam = Camera::new(5, 6);
+        cam.samples_per_pixel = 4;
+        cam.tile_size = 4;
+        cam.track_variance = true;
+        let accumulation = cam.render_accumulation(&world, &materials, &HittableList::new(), &[], &|_, _| {});
+        for j in 0..5 {
+            for i in 0..6 {
+                assert_eq!(accumulation.count(i, j), 4);
+                assert!((accumulation.sum(i, j) - Color::new(2.0, 2.0, 2.0)).length() < 1e-9);
+                assert!(accumulation.variance(i, j).unwrap().length() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats_count_primary_rays() {
+        let (world, materials) = glowing_wall();
+
+        let mut cam = Camera::new(5, 6);
+        cam.samples_per_pixel = 4;
+        cam.tile_size = 4;
+        cam.passes = 2;
+        let whole = Tile { x0: 0, x1: 6, y0: 0, y1: 5 };
+        let renderer = cam.renderer(&world, &materials, &HittableList::new(), &[]);
+        renderer.render_region(&whole, &|_, _| {});
+
+        // Every camera ray hits the light, which scatters nothing further.
+        let stats = renderer.stats();
+        assert_eq!(stats.counters.primary_rays, 5 * 6 * 4);
+        assert_eq!(stats.counters.primitive_tests, 5 * 6 * 4);
+        assert_eq!(stats.counters.secondary_rays + stats.counters.shadow_rays, 0);
+        assert_eq!(stats.tile_times.len(), 4 * 2);
+        assert!((0..5).all(|j| (0..6).all(|i| stats.pixel_cost(i, j) > 0.0)));
+    }
 
-    fn sample_square(&self) -> Vec3 {
-        // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-        Vec3::new(random_double(0.0, 1.0) - 0.5, random_double(0.0, 1.0) - 0.5, 0.0)
+    #[test]
+    fn test_passes_split_the_samples() {
+        let (world, materials) = glowing_wall();
+
+        let mut cam = Camera::new(5, 6);
+        cam.samples_per_pixel = 16;
+        cam.passes = 3;
+        let whole = Tile { x0: 0, x1: 6, y0: 0, y1: 5 };
+        let mut counts = Vec::new();
+        let image = cam.renderer(&world, &materials, &HittableList::new(), &[]).render_passes(&whole, &|_, _| {}, &mut |image, pass| counts.push((pass, image.count(5, 4))));
+        assert_eq!(counts, vec![(1, 5), (2, 10), (3, 16)]);
+        assert_eq!(image.count(0, 0), 16);
+
+        // Ten samples give nine strata, so no more than nine passes.
+        cam.samples_per_pixel = 10;
+        cam.passes = 16;
+        assert_eq!(cam.effective_passes(), 9);
+        let mut passes = 0;
+        cam.renderer(&world, &materials, &HittableList::new(), &[]).render_passes(&whole, &|_, _| {}, &mut |_, pass| passes = pass);
+        assert_eq!(passes, 9);
     }
 }
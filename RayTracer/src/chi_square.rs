@@ -0,0 +1,176 @@
+// Statistical checks that a Pdf draws directions with the density its value reports, for tests.
+//
+// The sphere of directions is cut into cells of equal solid angle, by cos(theta) and phi. The
+// directions drawn by generate are counted per cell, and compared with the counts value predicts,
+// found by integrating value over each cell, using Pearson's chi-square test. Cells expecting too
+// few directions for the test, including any where value is zero, are pooled together first.
+
+use crate::pdf::Pdf;
+use crate::vec3::Vec3;
+use std::f64::consts::PI;
+
+const COS_THETA_CELLS: usize = 16;
+const PHI_CELLS: usize = 32;
+const SUBDIVISIONS: usize = 16; // Points per cell side when integrating value over a cell
+const MIN_EXPECTED: f64 = 5.0; // Fewest directions expected in a cell, after pooling
+const SIGNIFICANCE: f64 = 0.01;
+
+fn cell_of(direction: &Vec3) -> usize {
+    let d = direction.unit();
+    let cos_theta = d.z.clamp(-1.0, 1.0);
+    let phi = d.y.atan2(d.x).rem_euclid(2.0 * PI);
+    let i = (((cos_theta + 1.0) / 2.0 * COS_THETA_CELLS as f64) as usize).min(COS_THETA_CELLS - 1);
+    let j = ((phi / (2.0 * PI) * PHI_CELLS as f64) as usize).min(PHI_CELLS - 1);
+    i * PHI_CELLS + j
+}
+
+fn direction_at(cos_theta: f64, phi: f64) -> Vec3 {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Probability of each cell under value, integrated with the midpoint rule on a grid of
+// subdivisions x subdivisions points per cell.
+fn cell_probabilities(pdf: &dyn Pdf, subdivisions: usize) -> Vec<f64> {
+    let cos_step = 2.0 / (COS_THETA_CELLS * subdivisions) as f64;
+    let phi_step = 2.0 * PI / (PHI_CELLS * subdivisions) as f64;
+    let mut probabilities = vec![0.0; COS_THETA_CELLS * PHI_CELLS];
+    for a in 0..COS_THETA_CELLS * subdivisions {
+        for b in 0..PHI_CELLS * subdivisions {
+            let cos_theta = -1.0 + (a as f64 + 0.5) * cos_step;
+            let phi = (b as f64 + 0.5) * phi_step;
+            let cell = (a / subdivisions) * PHI_CELLS + b / subdivisions;
+            probabilities[cell] += pdf.value(&direction_at(cos_theta, phi)) * cos_step * phi_step;
+        }
+    }
+    probabilities
+}
+
+// Integral of value over the whole sphere of directions, which is one for a density.
+pub fn integral(pdf: &dyn Pdf) -> f64 {
+    cell_probabilities(pdf, SUBDIVISIONS).iter().sum()
+}
+
+// Draws sample_count directions from pdf and tests them against its value. Returns why the test
+// failed, if it did.
+pub fn chi_square_test(pdf: &dyn Pdf, sample_count: usize) -> Result<(), String> {
+    let mut observed = vec![0.0; COS_THETA_CELLS * PHI_CELLS];
+    for _ in 0..sample_count {
+        let direction = pdf.generate();
+        if direction.near_zero() || direction.x.is_nan() || direction.y.is_nan() || direction.z.is_nan() {
+            return Err(format!("generated the direction ({}, {}, {})", direction.x, direction.y, direction.z));
+        }
+        observed[cell_of(&direction)] += 1.0;
+    }
+    let expected: Vec<f64> = cell_probabilities(pdf, SUBDIVISIONS).iter().map(|p| p * sample_count as f64).collect();
+
+    // Pool the cells expecting the fewest directions until every group expects enough.
+    let mut cells: Vec<usize> = (0..observed.len()).collect();
+    cells.sort_by(|&a, &b| expected[a].total_cmp(&expected[b]));
+    let mut chi_square = 0.0;
+    let mut groups = 0;
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for cell in cells {
+        if expected[cell] < MIN_EXPECTED || pooled_expected > 0.0 {
+            pooled_observed += observed[cell];
+            pooled_expected += expected[cell];
+            if pooled_expected < MIN_EXPECTED {
+                continue;
+            }
+            chi_square += (pooled_observed - pooled_expected) * (pooled_observed - pooled_expected) / pooled_expected;
+            (pooled_observed, pooled_expected) = (0.0, 0.0);
+        } else {
+            chi_square += (observed[cell] - expected[cell]) * (observed[cell] - expected[cell]) / expected[cell];
+        }
+        groups += 1;
+    }
+    if pooled_expected > 0.0 {
+        chi_square += (pooled_observed - pooled_expected) * (pooled_observed - pooled_expected) / pooled_expected;
+        groups += 1;
+    }
+    if groups < 2 {
+        return Ok(());
+    }
+
+    let p = chi_square_p_value(chi_square, (groups - 1) as f64);
+    if p < SIGNIFICANCE {
+        return Err(format!("chi-square {:.1} over {} groups, p = {:.2e}", chi_square, groups, p));
+    }
+    Ok(())
+}
+
+// Chance of a chi-square statistic at least this large with dof degrees of freedom.
+pub fn chi_square_p_value(chi_square: f64, dof: f64) -> f64 {
+    upper_incomplete_gamma(dof / 2.0, chi_square / 2.0)
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation.
+    const COEFFICIENTS: [f64; 6] = [76.18009172947146, -86.50532032941677, 24.01409824083091, -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for (k, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + k as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+fn upper_incomplete_gamma(a: f64, x: f64) -> f64 {
+    // Regularized Q(a, x), from its series below a + 1 and its continued fraction above.
+    if x <= 0.0 {
+        return 1.0;
+    }
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // Lentz's method.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for n in 1..1000 {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (-x + a * x.ln() - ln_gamma(a)).exp() * h
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p_values() {
+        // Values from chi-square tables.
+        assert!((chi_square_p_value(3.841, 1.0) - 0.05).abs() < 1e-4);
+        assert!((chi_square_p_value(18.307, 10.0) - 0.05).abs() < 1e-4);
+        assert!((chi_square_p_value(124.342, 100.0) - 0.05).abs() < 1e-4);
+        assert!((chi_square_p_value(2.558, 10.0) - 0.99).abs() < 1e-4);
+    }
+}
@@ -1,8 +1,6 @@
-use crate::material::{Isotropic, Material};
+use crate::material::MaterialId;
 use crate::vec3::Vec3;
-type Color = Vec3;
 use crate::hit::{HitRecord, Hittable, HittableClone};
-use crate::texture::Texture;
 use crate::interval::{self, Interval};
 use crate::aabb::AABB;
 use crate::ray::Ray;
@@ -13,23 +11,15 @@ use std::sync::Arc;
 pub struct ConstantMedium {
     boundary: Arc<dyn Hittable + Send + Sync>,
     neg_inv_density: f64,
-    phase_function: Arc<dyn Material + Send + Sync>,
+    phase_function: MaterialId, // Usually an Isotropic
 }
 
 impl ConstantMedium {
-    pub fn new(boundary: Arc<dyn Hittable + Send + Sync>, density: f64, tex: Arc<dyn Texture + Send + Sync>) -> Self {
+    pub fn new(boundary: Arc<dyn Hittable + Send + Sync>, density: f64, phase_function: MaterialId) -> Self {
         Self {
             boundary,
             neg_inv_density: -1.0 / density,
-            phase_function: Arc::new(Isotropic::with_texture(tex)),
-        }
-    }
-
-    pub fn new_with_albedo(boundary: Arc<dyn Hittable + Send + Sync>, density: f64, albedo: Color) -> Self {
-        Self {
-            boundary,
-            neg_inv_density: -1.0 / density,
-            phase_function: Arc::new(Isotropic::new(albedo)),
+            phase_function,
         }
     }
 }
@@ -84,7 +74,10 @@ impl Hittable for ConstantMedium {
 
         rec.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
         rec.front_face = true; // also arbitrary
-        rec.mat = Some(self.phase_function.clone());
+        rec.dpdu = Vec3::zero();
+        rec.dpdv = Vec3::zero();
+        rec.shading = None;
+        rec.mat = Some(self.phase_function);
 
         true
     }
@@ -0,0 +1,235 @@
+use crate::hit::{HitRecord, Hittable, HittableClone};
+use crate::aabb::AABB;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use std::sync::Arc;
+
+// Upper bound on the surface crossings collected from one child along a ray.
+const MAX_CROSSINGS: usize = 64;
+// Distance to step past a crossing before looking for the next one.
+const CROSSING_EPSILON: f64 = 0.0001;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference, // a with b cut away
+}
+
+impl CsgOp {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Boolean combination of two closed hittables. Both children must be closed surfaces
+// whose hits report front_face on entry and !front_face on exit.
+#[derive(Clone)]
+pub struct Csg {
+    a: Arc<dyn Hittable + Send + Sync>,
+    b: Arc<dyn Hittable + Send + Sync>,
+    op: CsgOp,
+    bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(a: Arc<dyn Hittable + Send + Sync>, b: Arc<dyn Hittable + Send + Sync>, op: CsgOp) -> Self {
+        let box_a = a.bounding_box();
+        let box_b = b.bounding_box();
+        let bbox = match op {
+            CsgOp::Union => AABB::from_aabbs(&box_a, &box_b),
+            CsgOp::Intersection => AABB::from_intervals(
+                Interval::with_values(box_a.x.min.max(box_b.x.min), box_a.x.max.min(box_b.x.max)),
+                Interval::with_values(box_a.y.min.max(box_b.y.min), box_a.y.max.min(box_b.y.max)),
+                Interval::with_values(box_a.z.min.max(box_b.z.min), box_a.z.max.min(box_b.z.max)),
+            ),
+            CsgOp::Difference => box_a,
+        };
+        Csg { a, b, op, bbox }
+    }
+
+    pub fn union(a: Arc<dyn Hittable + Send + Sync>, b: Arc<dyn Hittable + Send + Sync>) -> Self {
+        Csg::new(a, b, CsgOp::Union)
+    }
+
+    pub fn intersection(a: Arc<dyn Hittable + Send + Sync>, b: Arc<dyn Hittable + Send + Sync>) -> Self {
+        Csg::new(a, b, CsgOp::Intersection)
+    }
+
+    pub fn difference(a: Arc<dyn Hittable + Send + Sync>, b: Arc<dyn Hittable + Send + Sync>) -> Self {
+        Csg::new(a, b, CsgOp::Difference)
+    }
+
+    fn crossings(object: &Arc<dyn Hittable + Send + Sync>, r: &Ray) -> Vec<HitRecord> {
+        // Every surface crossing of the object along the whole line of the ray, in order.
+        let mut crossings = Vec::new();
+        let mut t_min = f64::NEG_INFINITY;
+        let mut rec = HitRecord::default();
+
+        while crossings.len() < MAX_CROSSINGS && object.hit(r, Interval::with_values(t_min, f64::INFINITY), &mut rec) {
+            t_min = rec.t + CROSSING_EPSILON;
+            crossings.push(rec.clone());
+        }
+
+        crossings
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, Interval::with_values(f64::NEG_INFINITY, ray_t.max)) {
+            return false;
+        }
+
+        let hits_a = Csg::crossings(&self.a, r);
+        let hits_b = Csg::crossings(&self.b, r);
+
+        // Walk the crossings of both children in order, tracking whether the ray is inside each,
+        // and report the first place where the combined inside/outside state flips.
+        let (mut ia, mut ib) = (0, 0);
+        let (mut in_a, mut in_b) = (false, false);
+        let mut inside = false;
+
+        while ia < hits_a.len() || ib < hits_b.len() {
+            let from_a = ib >= hits_b.len() || (ia < hits_a.len() && hits_a[ia].t <= hits_b[ib].t);
+            let crossing = if from_a {
+                ia += 1;
+                in_a = hits_a[ia - 1].front_face;
+                &hits_a[ia - 1]
+            } else {
+                ib += 1;
+                in_b = hits_b[ib - 1].front_face;
+                &hits_b[ib - 1]
+            };
+
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+
+            if crossing.t >= ray_t.max {
+                return false;
+            }
+            if !ray_t.surrounds(crossing.t) {
+                continue;
+            }
+
+            // Recover the child's outward normal; surfaces cut away by b face the other way.
+            let mut outward_normal = if crossing.front_face { crossing.normal } else { -1.0 * crossing.normal };
+            if self.op == CsgOp::Difference && !from_a {
+                outward_normal = -1.0 * outward_normal;
+            }
+
+            *rec = crossing.clone();
+            rec.set_face_normal(r, &outward_normal);
+            return true;
+        }
+
+        false
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+impl HittableClone for Csg {
+    fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant_medium::ConstantMedium;
+    use crate::material::{Isotropic, Materials};
+    use crate::rtweekend::seed_random;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+
+    fn sphere(x: f64, radius: f64) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), radius, None))
+    }
+
+    fn first_hit(object: &Csg, origin: Vec3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        if object.hit(&Ray::new(origin, direction, 0.0), Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_difference_hits_inside_of_hole() {
+        // Big sphere with a smaller one cut out of its front: the ray enters the hole, then
+        // hits the bottom of the hole from outside the result.
+        let shape = Csg::difference(sphere(0.0, 2.0), sphere(-2.0, 1.0));
+        let rec = first_hit(&shape, Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+
+        assert!((rec.t - 4.0).abs() < 1e-6);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_intersection_lens() {
+        let shape = Csg::intersection(sphere(-0.5, 1.0), sphere(0.5, 1.0));
+        let rec = first_hit(&shape, Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-6);
+
+        // Leaving the lens from inside.
+        let rec = first_hit(&shape, Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-6);
+        assert!(!rec.front_face);
+
+        // Passing beside the lens but through one of the spheres.
+        assert!(first_hit(&shape, Vec3::new(1.2, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_union_skips_internal_surfaces() {
+        let shape = Csg::union(sphere(-0.5, 1.0), sphere(0.5, 1.0));
+        let rec = first_hit(&shape, Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bounds_a_constant_medium() {
+        // Fog shaped by a difference and by an intersection scatters only inside the combined
+        // volume, never in the hole or in the parts of the spheres outside the lens.
+        seed_random(277);
+        let fog = Materials::new().add(Isotropic::new(Vec3::new(1.0, 1.0, 1.0)));
+        let holed = ConstantMedium::new(Arc::new(Csg::difference(sphere(0.0, 2.0), sphere(-2.0, 1.0))), 2.0, fog);
+        let lens = ConstantMedium::new(Arc::new(Csg::intersection(sphere(-0.5, 1.0), sphere(0.5, 1.0))), 2.0, fog);
+
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let mut scattered = 0;
+        for _ in 0..1000 {
+            let mut rec = HitRecord::default();
+            if holed.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+                assert!(rec.p.x > -1.0 - 1e-9 && rec.p.x < 2.0 + 1e-9, "scattered at x = {}", rec.p.x);
+                scattered += 1;
+            }
+            let mut rec = HitRecord::default();
+            if lens.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+                assert!(rec.p.x.abs() < 0.5 + 1e-9, "scattered at x = {}", rec.p.x);
+                scattered += 1;
+            }
+        }
+        assert!(scattered > 1000);
+
+        // Through the right sphere alone, beside the lens.
+        let beside = Ray::new(Vec3::new(1.2, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        for _ in 0..100 {
+            let mut rec = HitRecord::default();
+            assert!(!lens.hit(&beside, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+        }
+    }
+}
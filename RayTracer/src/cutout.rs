@@ -0,0 +1,106 @@
+use crate::hit::{HitRecord, Hittable, HittableClone};
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+use crate::aabb::AABB;
+use crate::hit_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Materials;
+use crate::ray::Ray;
+use crate::texture::ImageTexture;
+use std::sync::Arc;
+
+// Distance to step past a see-through hit before looking for the next one.
+const CUTOUT_EPSILON: f64 = 0.0001;
+
+// Cuts the wrapped object out along an image's alpha channel, such as leaves on a quad: where the
+// coverage is below the threshold the surface isn't there, and rays go on to whatever is behind.
+#[derive(Clone)]
+pub struct Cutout {
+    object: Arc<dyn Hittable + Send + Sync>,
+    coverage: Arc<ImageTexture>,
+    threshold: f64,
+}
+
+impl Cutout {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, coverage: Arc<ImageTexture>) -> Self {
+        Cutout { object, coverage, threshold: 0.5 }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl Hittable for Cutout {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let mut ray_t = ray_t;
+        loop {
+            if !self.object.hit(r, ray_t, rec) {
+                return false;
+            }
+            rec.set_footprint(r);
+            if self.coverage.alpha(rec) >= self.threshold {
+                return true;
+            }
+            ray_t = Interval::with_values(rec.t + CUTOUT_EPSILON, ray_t.max);
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(origin)
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        self.object.collect_emitters(materials, lights);
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        self.object.emitted_power(materials)
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        self.object.sample_surface()
+    }
+}
+
+impl HittableClone for Cutout {
+    fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad::Quad;
+    use crate::rtw::RtwImage;
+
+    #[test]
+    fn test_rays_pass_through_transparent_texels() {
+        // The left half of the quad is opaque and the right half clear, with another quad behind.
+        let half = ImageTexture::from_image(RtwImage::from_pixels(2, 1, vec![[1.0, 1.0, 1.0, 1.0], [1.0, 1.0, 1.0, 0.0]]));
+        let front = Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), None);
+        let back = Quad::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), None);
+        let mut world = HittableList::new();
+        world.add(Arc::new(Cutout::new(Arc::new(front), Arc::new(half))));
+        world.add(Arc::new(back));
+
+        let first_t = |x: f64| {
+            let r = Ray::new(Point3::new(x, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+            let mut rec = HitRecord::default();
+            assert!(world.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+            rec.t
+        };
+        assert!((first_t(0.25) - 1.0).abs() < 1e-9);
+        assert!((first_t(0.75) - 2.0).abs() < 1e-9);
+    }
+}
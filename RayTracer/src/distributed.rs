@@ -0,0 +1,404 @@
+// Rendering one image on several machines. A coordinator listens for workers, tells each the
+// scene to build, and hands out tiles one at a time; workers render them and send the raw samples
+// back. The protocol is a line of text per message, with results as binary accumulations:
+//
+//     coordinator: SCENE <seed> <description>
+//     coordinator: TILE <x0> <x1> <y0> <y1>
+//     worker:      WORKING, every few seconds while it renders
+//     worker:      DONE, followed by the tile's accumulation
+//     coordinator: BYE, once every tile is in
+//
+// Scenes are built from their description on both sides, after seeding the random numbers with
+// the same seed, so scenes with random parts come out the same everywhere. A tile whose worker
+// disconnects, or stays silent for longer than the tile timeout, goes back in the queue for
+// another worker. Workers say WORKING while they render, so slow tiles don't time out, but a
+// machine that drops off the network without closing its connection does.
+
+use crate::accumulation::Accumulation;
+use crate::rtweekend::{random_u64, seed_random};
+use crate::scene::Scene;
+use crate::scheduler::{tiles, Tile, TileOrder};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+// How often a worker rendering a tile tells the coordinator it is still there.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+// How long the coordinator waits on a silent worker by default, a good many heartbeats.
+const DEFAULT_TILE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Turns a scene description into the scene, like scenes::from_description.
+pub type SceneBuilder = fn(&str) -> Option<Scene>;
+
+pub struct Coordinator {
+    listener: TcpListener,
+    description: String,
+    seed: u64,
+    width: u32,
+    height: u32,
+    track_variance: bool,
+    tile_size: u32, // Width and height of the tiles handed to workers, each split again for their threads
+    tile_timeout: Duration, // Longest a worker may stay silent before its tile goes to another
+}
+
+// Work left and the image so far, shared by the threads talking to the workers.
+struct Progress {
+    queue: VecDeque<Tile>,
+    remaining: usize, // Tiles handed out or queued whose samples aren't in yet
+    pixels_done: usize,
+    image: Accumulation,
+}
+
+impl Coordinator {
+    pub fn bind(addr: impl ToSocketAddrs, description: &str, build: SceneBuilder) -> io::Result<Self> {
+        // The scene is built here once just to check the description and learn the image size.
+        let scene = build(description).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown scene \"{}\"", description)))?;
+        Ok(Coordinator {
+            listener: TcpListener::bind(addr)?,
+            description: description.to_string(),
+            seed: random_u64(),
+            width: scene.camera.image_width(),
+            height: scene.camera.image_height(),
+            track_variance: scene.camera.track_variance,
+            tile_size: 64,
+            tile_timeout: DEFAULT_TILE_TIMEOUT,
+        })
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    pub fn with_tile_timeout(mut self, tile_timeout: Duration) -> Self {
+        self.tile_timeout = tile_timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // Serves workers until every tile is in, and returns the whole image. progress is called with
+    // the number of pixels done and the total as tiles come back.
+    pub fn run(self, progress: impl Fn(usize, usize) + Sync) -> io::Result<Accumulation> {
+        let queue: VecDeque<Tile> = tiles(self.width, self.height, self.tile_size, TileOrder::Spiral).into();
+        let state = Mutex::new(Progress {
+            remaining: queue.len(),
+            queue,
+            pixels_done: 0,
+            image: Accumulation::new(self.width, self.height, self.track_variance).with_scene(Scene::fingerprint(&self.description, self.seed)),
+        });
+        let changed = Condvar::new();
+        let total = self.width as usize * self.height as usize;
+
+        // Polling the listener lets this thread notice when the last tile is in.
+        self.listener.set_nonblocking(true)?;
+        std::thread::scope(|s| -> io::Result<()> {
+            loop {
+                if state.lock().unwrap().remaining == 0 {
+                    return Ok(());
+                }
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        let (state, changed, progress) = (&state, &changed, &progress);
+                        let coordinator = &self;
+                        s.spawn(move || {
+                            // A worker failing only loses the tile it had, which serve requeues.
+                            let _ = coordinator.serve(stream, state, changed, &|done| progress(done, total));
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(10)),
+                    Err(e) => return Err(e),
+                }
+            }
+        })?;
+
+        Ok(state.into_inner().unwrap().image)
+    }
+
+    fn serve(&self, stream: TcpStream, state: &Mutex<Progress>, changed: &Condvar, progress: &dyn Fn(usize)) -> io::Result<()> {
+        // Talks to one worker until the image is done or the worker is lost.
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(self.tile_timeout))?;
+        let mut reader = BufReader::new(&stream);
+        let mut writer = &stream;
+        writeln!(writer, "SCENE {} {}", self.seed, self.description)?;
+
+        loop {
+            // Waits while the queue is empty but other workers may still give tiles back.
+            let tile = {
+                let mut progress = changed.wait_while(state.lock().unwrap(), |p| p.queue.is_empty() && p.remaining > 0).unwrap();
+                match progress.queue.pop_front() {
+                    Some(tile) => tile,
+                    None => {
+                        writeln!(writer, "BYE")?;
+                        return Ok(());
+                    }
+                }
+            };
+
+            match Self::render_remotely(&tile, &mut reader, &mut writer) {
+                Ok(samples) => {
+                    let pixels_done = {
+                        let mut state = state.lock().unwrap();
+                        state.image.add_at(tile.x0, tile.y0, &samples);
+                        state.remaining -= 1;
+                        state.pixels_done += tile.pixel_count();
+                        state.pixels_done
+                    };
+                    changed.notify_all();
+                    progress(pixels_done);
+                }
+                Err(e) => {
+                    state.lock().unwrap().queue.push_back(tile);
+                    changed.notify_all();
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn render_remotely(tile: &Tile, reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<Accumulation> {
+        writeln!(writer, "TILE {} {} {} {}", tile.x0, tile.x1, tile.y0, tile.y1)?;
+        let mut line = String::new();
+        // Every heartbeat starts the read timeout over.
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            if line.trim() != "WORKING" {
+                break;
+            }
+        }
+        if line.trim() != "DONE" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("expected DONE, got \"{}\"", line.trim())));
+        }
+        // The size is checked before anything is allocated, so a confused worker is just dropped.
+        Accumulation::read_from_expecting(reader, tile.width(), tile.height())
+    }
+}
+
+// Renders tiles for the coordinator at addr until it says the image is done, building scenes with
+// build. Returns the number of tiles rendered.
+pub fn run_worker(addr: impl ToSocketAddrs, build: SceneBuilder) -> io::Result<usize> {
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let (seed, description) = line
+        .trim()
+        .strip_prefix("SCENE ")
+        .and_then(|rest| rest.split_once(' '))
+        .and_then(|(seed, description)| Some((seed.parse::<u64>().ok()?, description.to_string())))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("expected SCENE, got \"{}\"", line.trim())))?;
+
+    // Photons are shot here too, so every worker gathers from the same photon map and the caustics
+    // don't change from tile to tile. The render threads draw their own random numbers.
+    seed_random(seed);
+    let mut scene = build(&description).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown scene \"{}\"", description)))?;
+    let renderer = scene.camera.renderer(&scene.world, &scene.materials, &scene.extra_targets, &scene.delta_lights);
+
+    let mut rendered = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(rendered);
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["BYE"] => return Ok(rendered),
+            ["TILE", x0, x1, y0, y1] => {
+                let parse = |word: &str| word.parse::<u32>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                let tile = Tile { x0: parse(x0)?, x1: parse(x1)?, y0: parse(y0)?, y1: parse(y1)? };
+                let (finished, heartbeats) = mpsc::channel::<()>();
+                let samples = std::thread::scope(|s| -> io::Result<Accumulation> {
+                    // Heartbeats stop before DONE goes out, so the two never interleave.
+                    let mut writer = writer;
+                    let heartbeat = s.spawn(move || -> io::Result<()> {
+                        while heartbeats.recv_timeout(HEARTBEAT_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+                            writeln!(writer, "WORKING")?;
+                        }
+                        Ok(())
+                    });
+                    let samples = renderer.render_region(&tile, &|_, _| {});
+                    drop(finished);
+                    heartbeat.join().unwrap()?;
+                    Ok(samples)
+                })?;
+                let mut message = b"DONE\n".to_vec();
+                samples.write_to(&mut message)?;
+                writer.write_all(&message)?;
+                rendered += 1;
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected \"{}\"", line.trim()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::scenes::glowing_wall;
+    use crate::vec3::Vec3;
+
+    fn glow(description: &str) -> Option<Scene> {
+        // A light filling the view, so every sample is the same.
+        if description != "glow" {
+            return None;
+        }
+        let (world, materials) = glowing_wall();
+        let mut cam = Camera::new(13, 21);
+        cam.samples_per_pixel = 4;
+        cam.threads = 2;
+        Some(Scene::new(world, materials, cam))
+    }
+
+    #[test]
+    fn test_workers_on_localhost_render_every_tile() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", "glow", glow).unwrap().with_seed(7).with_tile_size(5);
+        let addr = coordinator.local_addr().unwrap();
+        let tile_count = tiles(21, 13, 5, TileOrder::Spiral).len();
+
+        std::thread::scope(|s| {
+            let image = s.spawn(move || coordinator.run(|_, _| {}));
+
+            // A worker that takes a tile and disappears, whose tile must go to someone else.
+            {
+                let stream = TcpStream::connect(addr).unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                assert!(line.starts_with("SCENE "));
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                assert!(line.starts_with("TILE "));
+            }
+
+            let workers: Vec<_> = (0..3).map(|_| s.spawn(move || run_worker(addr, glow).unwrap_or(0))).collect();
+            let rendered: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+            assert_eq!(rendered, tile_count);
+
+            let image = image.join().unwrap().unwrap();
+            assert_eq!(image.scene(), Scene::fingerprint("glow", 7));
+            for j in 0..13 {
+                for i in 0..21 {
+                    assert_eq!(image.count(i, j), 4);
+                    assert!((image.mean(i, j) - Vec3::new(0.5, 0.5, 0.5)).length() < 1e-9);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_tile_of_stalled_worker_is_requeued() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", "glow", glow).unwrap().with_tile_size(8).with_tile_timeout(Duration::from_millis(200));
+        let addr = coordinator.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let image = s.spawn(move || coordinator.run(|_, _| {}));
+
+            // Takes a tile and never answers, but stays connected.
+            let stalled = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(&stalled);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert!(line.starts_with("TILE "));
+
+            s.spawn(move || run_worker(addr, glow).unwrap());
+            let image = image.join().unwrap().unwrap();
+            assert!((0..13).all(|j| (0..21).all(|i| image.count(i, j) == 4)));
+        });
+    }
+
+    #[test]
+    fn test_heartbeats_keep_a_slow_tile() {
+        // One tile covers the image, so the slow worker gets BYE only if its tile was kept.
+        let coordinator = Coordinator::bind("127.0.0.1:0", "glow", glow).unwrap().with_tile_size(64).with_tile_timeout(Duration::from_millis(200));
+        let addr = coordinator.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let image = s.spawn(move || coordinator.run(|_, _| {}));
+
+            let slow = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(&slow);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line.trim(), "TILE 0 21 0 13");
+
+            // Renders for five timeouts, saying it is still there twice per timeout.
+            for _ in 0..10 {
+                std::thread::sleep(Duration::from_millis(100));
+                writeln!(&slow, "WORKING").unwrap();
+            }
+            let mut samples = Accumulation::new(21, 13, false);
+            for j in 0..13 {
+                for i in 0..21 {
+                    for _ in 0..4 {
+                        samples.add_sample(i, j, Vec3::new(0.5, 0.5, 0.5));
+                    }
+                }
+            }
+            let mut message = b"DONE\n".to_vec();
+            samples.write_to(&mut message).unwrap();
+            (&slow).write_all(&message).unwrap();
+
+            line.clear();
+            let _ = reader.read_line(&mut line);
+            if line.trim() != "BYE" {
+                // Let the coordinator finish before failing.
+                s.spawn(move || run_worker(addr, glow).unwrap());
+            }
+            assert_eq!(line.trim(), "BYE");
+            let image = image.join().unwrap().unwrap();
+            assert!((0..13).all(|j| (0..21).all(|i| image.count(i, j) == 4)));
+        });
+    }
+
+    #[test]
+    fn test_tile_of_worker_sending_garbage_is_requeued() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", "glow", glow).unwrap().with_tile_size(8);
+        let addr = coordinator.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let image = s.spawn(move || coordinator.run(|_, _| {}));
+
+            // Answers its tile with samples for the largest image there could be.
+            let confused = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(&confused);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert!(line.starts_with("TILE "));
+            let mut message = b"DONE\nRTAC".to_vec();
+            for value in [1, u32::MAX, u32::MAX, 0] {
+                message.extend_from_slice(&value.to_le_bytes());
+            }
+            (&confused).write_all(&message).unwrap();
+
+            s.spawn(move || run_worker(addr, glow).unwrap());
+            let image = image.join().unwrap().unwrap();
+            assert!((0..13).all(|j| (0..21).all(|i| image.count(i, j) == 4)));
+        });
+    }
+
+    #[test]
+    fn test_unknown_scene_is_refused() {
+        assert!(Coordinator::bind("127.0.0.1:0", "nothing", glow).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::color::write_color;
+use crate::vec3::Vec3;
+type Color = Vec3;
+use image::{ImageBuffer, ImageResult, RgbImage};
+use std::fs::File;
+use std::io::Cursor;
+
+// Linear radiance of every pixel of a rendered image, row by row from the top left.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        FrameBuffer { width, height, pixels: vec![Color::zero(); width as usize * height as usize] }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        j as usize * self.width as usize + i as usize
+    }
+
+    pub fn get(&self, i: u32, j: u32) -> Color {
+        self.pixels[self.index(i, j)]
+    }
+
+    pub fn set(&mut self, i: u32, j: u32, color: Color) {
+        let index = self.index(i, j);
+        self.pixels[index] = color;
+    }
+
+    pub fn to_rgb_image(&self) -> RgbImage {
+        // Gamma corrected and clamped to bytes.
+        let mut img: RgbImage = ImageBuffer::new(self.width, self.height);
+        let width = self.width as usize;
+        for (index, color) in self.pixels.iter().enumerate() {
+            write_color(*color, &mut img, index % width, index / width);
+        }
+        img
+    }
+
+    pub fn save_jpeg(&self, path: &str, quality: u8) -> ImageResult<()> {
+        let output_image = image::DynamicImage::ImageRgb8(self.to_rgb_image());
+        let mut output_file = File::create(path)?;
+        output_image.write_to(&mut output_file, image::ImageOutputFormat::Jpeg(quality))
+    }
+
+    pub fn encode_png(&self) -> ImageResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(self.to_rgb_image()).write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+        Ok(bytes)
+    }
+}
@@ -2,9 +2,12 @@ use crate::ray::Ray;
 use crate::vec3::Vec3;
 type Point3 = Vec3;
 use crate::interval::Interval;
-use crate::material::Material;
-use std::f64::INFINITY;
+use crate::material::{MaterialId, Materials};
+use crate::bump::ShadingNormal;
+use crate::onb::ONB;
 use crate::aabb::AABB;
+use crate::hit_list::HittableList;
+use crate::primitive::Primitive;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -15,7 +18,19 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
-    pub mat: Option<Arc<dyn Material + Send + Sync>>,
+    pub mat: Option<MaterialId>,
+    pub dpdu: Vec3, // Change of p along the texture u direction, zero if the surface has none
+    pub dpdv: Vec3, // Change of p along the texture v direction
+    pub footprint: UvFootprint,
+    pub shading: Option<Arc<dyn ShadingNormal + Send + Sync>>, // Normal or bump map to apply before scattering
+}
+
+// Ellipse covered by one pixel around the hit point in texture space, given by its two axes.
+// Both axes are zero for a point lookup.
+#[derive(Clone, Copy, Default)]
+pub struct UvFootprint {
+    pub major: (f64, f64),
+    pub minor: (f64, f64),
 }
 
 impl Default for HitRecord {
@@ -28,6 +43,10 @@ impl Default for HitRecord {
             v: 0.0,
             front_face: false,
             mat: None,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            footprint: UvFootprint::default(),
+            shading: None,
         }
     }
 }
@@ -36,13 +55,73 @@ impl HitRecord {
         // Sets the hit record normal vector.
         // NOTE: the parameter `outward_normal` is assumed to have unit length.
 
-        self.front_face = r.direction().dot(outward_normal.clone()) < 0.0;
+        self.front_face = r.direction().dot(*outward_normal) < 0.0;
         self.normal = if self.front_face {
-            outward_normal.clone()
+            *outward_normal
         } else {
-            outward_normal.clone() * (-1.0)
+            *outward_normal * (-1.0)
+        };
+    }
+
+    pub fn set_footprint(&mut self, r: &Ray) {
+        // Project the ray's pixel cone onto the surface and express it in uv coordinates.
+        self.footprint = UvFootprint::default();
+
+        let width = r.footprint_width(self.t);
+        if width <= 0.0 || self.dpdu.near_zero() || self.dpdv.near_zero() {
+            return;
+        }
+
+        // The footprint stretches along the ray's direction across the surface as the view grazes it.
+        let d = r.direction().unit();
+        let cosine = d.dot(self.normal).abs().max(0.05);
+        let along = d - d.dot(self.normal) * self.normal;
+        let major_dir = if along.near_zero() { self.dpdu.unit() } else { along.unit() };
+        let minor_dir = self.normal.cross(major_dir);
+
+        self.footprint = UvFootprint {
+            major: self.world_to_uv(major_dir * (width / cosine)),
+            minor: self.world_to_uv(minor_dir * width),
         };
     }
+
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        // Unit tangent and bitangent completing the normal to a frame, following dpdu and dpdv where
+        // the surface has them.
+        let n = self.normal;
+        let tangent = self.dpdu - self.dpdu.dot(n) * n;
+        if tangent.near_zero() {
+            let onb = ONB::build_from_w(&n);
+            return (onb.u(), onb.v());
+        }
+        let tangent = tangent.unit();
+
+        let bitangent = self.dpdv - self.dpdv.dot(n) * n - self.dpdv.dot(tangent) * tangent;
+        let bitangent = if bitangent.near_zero() { n.cross(tangent) } else { bitangent.unit() };
+        (tangent, bitangent)
+    }
+
+    pub fn apply_shading_normal(&mut self) {
+        // Replace the geometric normal by the shading normal of the surface's normal or bump map.
+        if let Some(shading) = self.shading.take() {
+            self.normal = shading.shading_normal(self);
+        }
+    }
+
+    fn world_to_uv(&self, x: Vec3) -> (f64, f64) {
+        // Least-squares solve of x = du * dpdu + dv * dpdv.
+        let a = self.dpdu.dot(self.dpdu);
+        let b = self.dpdu.dot(self.dpdv);
+        let c = self.dpdv.dot(self.dpdv);
+        let det = a * c - b * b;
+        if det.abs() < 1e-12 {
+            return (0.0, 0.0);
+        }
+
+        let xu = x.dot(self.dpdu);
+        let xv = x.dot(self.dpdv);
+        ((c * xu - b * xv) / det, (a * xv - b * xu) / det)
+    }
 }
 
 pub trait HittableClone {
@@ -51,12 +130,27 @@ pub trait HittableClone {
 pub trait Hittable: HittableClone {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
     fn bounding_box(&self) -> AABB;
-    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
         0.0
     }
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, _origin: &Point3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // Adds every emissive part of this object that supports pdf_value and random to lights.
+    fn collect_emitters(&self, _materials: &Materials, _lights: &mut HittableList) {}
+    // Total power emitted by the object, zero if it does not emit.
+    fn emitted_power(&self, _materials: &Materials) -> f64 {
+        0.0
+    }
+    // A point spread uniformly over the surface by area, with the unit outward normal there. Objects
+    // that can't be sampled this way give None and are never used to start light paths.
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        None
+    }
+    // The object as a Primitive, for the built-in shapes that can be held by value in the BVH.
+    fn as_primitive(&self) -> Option<Primitive> {
+        None
+    }
 }
 
 
@@ -93,6 +187,31 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&(*origin - self.offset), direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(&(*origin - self.offset))
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        let mut emitters = HittableList::new();
+        self.object.collect_emitters(materials, &mut emitters);
+        for emitter in emitters.objects {
+            lights.add(Arc::new(Translate::new(emitter, self.offset)));
+        }
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        self.object.emitted_power(materials)
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        let (p, normal) = self.object.sample_surface()?;
+        Some((p + self.offset, normal))
+    }
 }
 
 impl HittableClone for Translate {
@@ -115,8 +234,8 @@ impl RotateY {
         let cos_theta = radians.cos();
         let bbox = object.bounding_box();
 
-        let mut min = Point3::new( INFINITY,  INFINITY,  INFINITY);
-        let mut max = Point3::new(-INFINITY, -INFINITY, -INFINITY);
+        let mut min = Point3::new( f64::INFINITY,  f64::INFINITY,  f64::INFINITY);
+        let mut max = Point3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
 
         for i in 0..2 {
             for j in 0..2 {
@@ -149,6 +268,14 @@ impl RotateY {
             bbox,
         }
     }
+
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * v.x() - self.sin_theta * v.z(), v.y(), self.sin_theta * v.x() + self.cos_theta * v.z())
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * v.x() + self.sin_theta * v.z(), v.y(), -self.sin_theta * v.x() + self.cos_theta * v.z())
+    }
 }
 impl Hittable for RotateY {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
@@ -172,15 +299,17 @@ impl Hittable for RotateY {
         // Change the intersection point from object space to world space
         let mut p = rec.p;
         p.x = self.cos_theta * rec.p.x() + self.sin_theta * rec.p.z();
-        p.z = -1.0 * self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z();
+        p.z = -self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z();
 
         // Change the normal from object space to world space
         let mut normal = rec.normal;
         normal.x = self.cos_theta * rec.normal.x() + self.sin_theta * rec.normal.z();
-        normal.z = -1.0 * self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z();
+        normal.z = -self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z();
 
         rec.p = p;
         rec.normal = normal;
+        rec.dpdu = self.to_world(rec.dpdu);
+        rec.dpdv = self.to_world(rec.dpdv);
 
         true
     }
@@ -188,9 +317,128 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&self.to_object(*origin), &self.to_object(*direction))
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.to_world(self.object.random(&self.to_object(*origin)))
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        let mut emitters = HittableList::new();
+        self.object.collect_emitters(materials, &mut emitters);
+        let angle = self.sin_theta.atan2(self.cos_theta).to_degrees();
+        for emitter in emitters.objects {
+            lights.add(Arc::new(RotateY::new(emitter, angle)));
+        }
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        self.object.emitted_power(materials)
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        let (p, normal) = self.object.sample_surface()?;
+        Some((self.to_world(p), self.to_world(normal)))
+    }
 }
 impl HittableClone for RotateY {
     fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad::{make_box, Quad};
+    use crate::rtweekend::{random_double, seed_random};
+    use crate::sphere::Sphere;
+
+    fn rotate_y(v: Vec3, angle: f64) -> Vec3 {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Vec3::new(cos * v.x + sin * v.z, v.y, -sin * v.x + cos * v.z)
+    }
+
+    fn assert_same_hit(a: &HitRecord, b: &HitRecord) {
+        assert!((a.t - b.t).abs() < 1e-9, "t {} vs {}", a.t, b.t);
+        assert!((a.p - b.p).length() < 1e-9);
+        assert!((a.normal - b.normal).length() < 1e-9);
+        assert_eq!(a.front_face, b.front_face);
+        assert!((a.u - b.u).abs() < 1e-9 && (a.v - b.v).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_translate_matches_moved_sphere() {
+        seed_random(457);
+        for _ in 0..1000 {
+            let center = Point3::random(-3.0, 3.0);
+            let offset = Vec3::random(-5.0, 5.0);
+            let translated = Translate::new(Arc::new(Sphere::new(center, 1.0, None)), offset);
+            let moved = Sphere::new(center + offset, 1.0, None);
+
+            let origin = Point3::random(-10.0, 10.0);
+            let r = Ray::new(origin, center + offset + 0.9 * Vec3::random_unit_vector() - origin, 0.0);
+            let (mut a, mut b) = (HitRecord::default(), HitRecord::default());
+            let hit = translated.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut a);
+            assert_eq!(hit, moved.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut b));
+            if hit {
+                assert_same_hit(&a, &b);
+                assert!(translated.bounding_box().contains(a.p, 1e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate_y_matches_rotated_quad() {
+        // Normals and hit points of a rotated quad must be those of a quad built already rotated.
+        seed_random(458);
+        for _ in 0..1000 {
+            let (q, u, v) = (Point3::random(-3.0, 3.0), Vec3::random(-2.0, 2.0), Vec3::random(-2.0, 2.0));
+            if u.cross(v).length() < 0.5 {
+                continue;
+            }
+            let angle = random_double(-180.0, 180.0);
+            let rotated = RotateY::new(Arc::new(Quad::new(q, u, v, None)), angle);
+            let built = Quad::new(rotate_y(q, angle), rotate_y(u, angle), rotate_y(v, angle), None);
+
+            let target = rotate_y(q + random_double(0.05, 0.95) * u + random_double(0.05, 0.95) * v, angle);
+            let origin = Point3::random(-10.0, 10.0);
+            let r = Ray::new(origin, target - origin, 0.0);
+            let (mut a, mut b) = (HitRecord::default(), HitRecord::default());
+            let hit = rotated.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut a);
+            assert_eq!(hit, built.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut b));
+            if hit {
+                assert_same_hit(&a, &b);
+                assert!(rotated.bounding_box().contains(a.p, 1e-9));
+                assert!((a.dpdu - rotate_y(u, angle)).length() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotated_translated_box_from_outside() {
+        // The way the Cornell box blocks are placed: every hit from outside is on a front face,
+        // with a normal facing the ray, inside the bounding box.
+        seed_random(459);
+        let block = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), None));
+        for _ in 0..200 {
+            let angle = random_double(-90.0, 90.0);
+            let offset = Vec3::random(-100.0, 100.0);
+            let placed = Translate::new(Arc::new(RotateY::new(block.clone(), angle)), offset);
+
+            let inner = rotate_y(Point3::new(random_double(0.0, 165.0), random_double(0.0, 330.0), random_double(0.0, 165.0)), angle) + offset;
+            let origin = inner + 1000.0 * Vec3::random_unit_vector();
+            let r = Ray::new(origin, inner - origin, 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(placed.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+            assert!(rec.front_face);
+            assert!(rec.normal.dot(*r.direction()) < 0.0);
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+            assert!(placed.bounding_box().contains(rec.p, 1e-9));
+        }
+    }
+}
@@ -2,6 +2,7 @@ use crate::hit::{Hittable, HitRecord, HittableClone};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::aabb::AABB;
+use crate::material::Materials;
 use std::sync::Arc;
 use crate::rtweekend::random_int;
 use crate::vec3::Vec3;
@@ -13,6 +14,12 @@ pub struct HittableList {
     bbox: AABB,
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
@@ -21,6 +28,7 @@ impl HittableList {
         }
     }
 
+    #[allow(clippy::self_named_constructors)]
     pub fn hittable_list(object: Arc<dyn Hittable + Send + Sync>) -> Self {
         let mut list = HittableList::new();
         list.add(object);
@@ -44,22 +52,22 @@ impl HittableList {
 
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::default();
+        // Objects only fill in rec when they are hit, and each closer hit overwrites the last, so
+        // there is no need to hit into a separate record and copy it over.
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
         for object in &self.objects {
-            if object.hit(r, Interval::with_values(ray_t.min, closest_so_far), &mut temp_rec) {
+            if object.hit(r, Interval::with_values(ray_t.min, closest_so_far), rec) {
                 hit_anything = true;
-                closest_so_far = temp_rec.t.clone();
-                *rec = temp_rec.clone();
+                closest_so_far = rec.t;
             }
         }
 
         hit_anything
     }
     fn bounding_box(&self) -> AABB {
-        self.bbox.clone()
+        self.bbox
     }
 
     fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
@@ -77,6 +85,12 @@ impl Hittable for HittableList {
         let int_size = self.objects.len();
         self.objects[random_int(0, int_size as i32 - 1) as usize].random(origin)
     }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        for object in &self.objects {
+            object.collect_emitters(materials, lights);
+        }
+    }
 }
 
 impl HittableClone for HittableList {
@@ -84,3 +98,39 @@ impl HittableClone for HittableList {
         Arc::new(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BVHNode;
+    use crate::hit::Translate;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn test_collect_emitters() {
+        let mut materials = Materials::new();
+        let light = Some(materials.add(DiffuseLight::with_color(Vec3::new(4.0, 4.0, 4.0))));
+        let grey = Some(materials.add(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+
+        let mut objects = HittableList::new();
+        objects.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey)));
+        objects.add(Arc::new(Quad::new(Point3::new(-1.0, 5.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0), light)));
+        objects.add(Arc::new(Sphere::new(Point3::new(3.0, 0.0, 0.0), 1.0, grey)));
+
+        let mut world = HittableList::new();
+        world.add(Arc::new(BVHNode::new(&mut objects)));
+        let moved_light = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, light));
+        world.add(Arc::new(Translate::new(moved_light, Vec3::new(0.0, 0.0, 10.0))));
+
+        let mut lights = HittableList::new();
+        world.collect_emitters(&materials, &mut lights);
+        assert_eq!(lights.objects.len(), 2);
+
+        // The translated light is sampled where it ends up, not where it was built.
+        let toward = lights.objects[1].random(&Point3::zero());
+        assert!(toward.z > 0.0);
+        assert!(lights.objects[1].pdf_value(&Point3::zero(), &Vec3::new(0.0, 0.0, 1.0)) > 0.0);
+    }
+}
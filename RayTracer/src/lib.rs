@@ -0,0 +1,42 @@
+// Ray tracer as a library: build a Scene, or use one from scenes, and render it into a FrameBuffer.
+
+pub mod color;
+pub mod vec3;
+pub mod ray;
+pub mod hit;
+pub mod sphere;
+pub mod hit_list;
+pub mod rtweekend;
+pub mod interval;
+pub mod camera;
+pub mod material;
+pub mod aabb;
+pub mod bvh;
+pub mod texture;
+pub mod rtw;
+pub mod perlin;
+pub mod procedural;
+pub mod mapping;
+pub mod quad;
+pub mod primitive;
+pub mod constant_medium;
+pub mod onb;
+pub mod pdf;
+#[cfg(test)]
+mod chi_square;
+pub mod light;
+pub mod light_sampler;
+pub mod bdpt;
+pub mod photon_map;
+pub mod scheduler;
+pub mod distributed;
+pub mod sdf;
+pub mod csg;
+pub mod bump;
+pub mod cutout;
+pub mod framebuffer;
+pub mod accumulation;
+pub mod preview;
+pub mod stats;
+pub mod scene;
+pub mod scenes;
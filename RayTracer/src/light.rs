@@ -0,0 +1,161 @@
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+
+// Incoming light at a point from one delta light, before the shadow test.
+pub struct LightSample {
+    pub direction: Vec3, // Unit vector from the point towards the light
+    pub distance: f64, // Distance to the light along direction, infinite for directional lights
+    pub radiance: Color, // Light arriving at the point, with distance and cone falloff applied
+}
+
+// Lights with no area, that rays can never hit by chance and that must be sampled explicitly
+// with shadow rays.
+pub trait Light {
+    fn sample(&self, p: &Point3) -> Option<LightSample>;
+}
+
+#[derive(Clone)]
+pub struct PointLight {
+    position: Point3,
+    intensity: Color, // Power per unit solid angle
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        PointLight { position, intensity }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3, // Unit axis of the cone
+    intensity: Color, // Power per unit solid angle on the axis
+    cos_inner: f64, // Full intensity inside this angle
+    cos_outer: f64, // No light outside this angle
+}
+
+impl SpotLight {
+    // Cone angles are half angles in degrees, measured from the axis.
+    pub fn new(position: Point3, look_at: Point3, intensity: Color, inner_angle: f64, outer_angle: f64) -> Self {
+        let outer_angle = outer_angle.clamp(0.0, 180.0);
+        let inner_angle = inner_angle.clamp(0.0, outer_angle);
+        SpotLight {
+            position,
+            direction: (look_at - position).unit(),
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        // Smoothstep from the outer edge of the cone to the inner one.
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: falloff * self.intensity / distance_squared,
+        })
+    }
+}
+
+// Light from infinitely far away arriving along one direction, like the sun.
+#[derive(Clone)]
+pub struct DirectionalLight {
+    to_light: Vec3, // Unit vector pointing back towards the light
+    irradiance: Color, // Power per unit area on a surface facing the light
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        // direction is the way the light travels.
+        DirectionalLight { to_light: -1.0 * direction.unit(), irradiance }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Point3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: self.to_light,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_light_inverse_square() {
+        let light = PointLight::new(Point3::new(0.0, 4.0, 0.0), Color::new(16.0, 16.0, 16.0));
+        let sample = light.sample(&Point3::zero()).unwrap();
+        assert!((sample.direction - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert!((sample.distance - 4.0).abs() < 1e-12);
+        assert!((sample.radiance.x - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_spot_light_cone() {
+        let light = SpotLight::new(Point3::new(0.0, 1.0, 0.0), Point3::zero(), Color::new(1.0, 1.0, 1.0), 20.0, 30.0);
+        // On the axis, at the 25 degree edge, and outside the cone.
+        assert!((light.sample(&Point3::zero()).unwrap().radiance.x - 1.0).abs() < 1e-12);
+        let edge = light.sample(&Point3::new(25f64.to_radians().tan(), 0.0, 0.0)).unwrap();
+        assert!(edge.radiance.x > 0.0 && edge.radiance.x < 1.0);
+        assert!(light.sample(&Point3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_directional_light() {
+        let light = DirectionalLight::new(Vec3::new(0.0, -2.0, 0.0), Color::new(3.0, 3.0, 3.0));
+        let sample = light.sample(&Point3::new(5.0, 0.0, 5.0)).unwrap();
+        assert!((sample.direction - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert!(sample.distance.is_infinite());
+        assert_eq!(sample.radiance.x, 3.0);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::hit::{HitRecord, Hittable};
+use crate::hit_list::HittableList;
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::aabb::AABB;
+use crate::interval::Interval;
+use crate::material::Materials;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Offset along the normal from which a sampled light point is hit again to recover its hit record.
+const PROBE_OFFSET: f64 = 1e-4;
+
+// Deepest light tree pdf_value can walk without allocating. The median split halves the lights at
+// every level, so this is far more than any scene needs.
+const MAX_TREE_DEPTH: usize = 64;
+
+// How the light to sample is picked at each shading point.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LightSampling {
+    Uniform, // Every light equally often
+    Power, // In proportion to emitted power
+    Bvh, // By estimated contribution at the shading point, for scenes with many lights
+}
+
+// Chooses one of the sampled lights and a direction towards it, and gives the matching density.
+pub struct LightSampler {
+    lights: Vec<Arc<dyn Hittable + Send + Sync>>,
+    mode: LightSampling,
+    cdf: Vec<f64>, // Running total of selection weights for Uniform and Power
+    bvh: Vec<LightNode>, // Root first, only built for Bvh
+}
+
+#[derive(Clone, Copy)]
+enum LightNodeKind {
+    Leaf(usize), // Index into lights
+    Interior(usize, usize), // Indices into the node array
+}
+
+#[derive(Clone, Copy)]
+struct LightNode {
+    bbox: AABB,
+    power: f64,
+    kind: LightNodeKind,
+}
+
+impl LightSampler {
+    pub fn new(lights: HittableList, materials: &Materials, mode: LightSampling) -> Self {
+        let lights = lights.objects;
+        let weights = Self::weights(&lights, materials, mode);
+
+        let mut cdf = Vec::with_capacity(weights.len());
+        let mut total = 0.0;
+        for weight in &weights {
+            total += weight;
+            cdf.push(total);
+        }
+
+        let mut bvh = Vec::new();
+        if mode == LightSampling::Bvh && !lights.is_empty() {
+            let mut indices: Vec<usize> = (0..lights.len()).collect();
+            Self::build(&lights, &weights, &mut indices, &mut bvh);
+        }
+
+        LightSampler { lights, mode, cdf, bvh }
+    }
+
+    fn weights(lights: &[Arc<dyn Hittable + Send + Sync>], materials: &Materials, mode: LightSampling) -> Vec<f64> {
+        if mode == LightSampling::Uniform {
+            return vec![1.0; lights.len()];
+        }
+
+        // Importance targets that do not emit are given the mean power of the lights.
+        let powers: Vec<f64> = lights.iter().map(|light| light.emitted_power(materials).max(0.0)).collect();
+        let emitting: Vec<f64> = powers.iter().copied().filter(|&power| power > 0.0).collect();
+        let fallback = if emitting.is_empty() { 1.0 } else { emitting.iter().sum::<f64>() / emitting.len() as f64 };
+        powers.into_iter().map(|power| if power > 0.0 { power } else { fallback }).collect()
+    }
+
+    fn build(lights: &[Arc<dyn Hittable + Send + Sync>], weights: &[f64], indices: &mut [usize], nodes: &mut Vec<LightNode>) -> usize {
+        // Builds the subtree over indices and returns its node index.
+        let mut bbox = lights[indices[0]].bounding_box();
+        let mut power = 0.0;
+        for &i in indices.iter() {
+            bbox = AABB::from_aabbs(&bbox, &lights[i].bounding_box());
+            power += weights[i];
+        }
+
+        let node = nodes.len();
+        if indices.len() == 1 {
+            nodes.push(LightNode { bbox, power, kind: LightNodeKind::Leaf(indices[0]) });
+            return node;
+        }
+        nodes.push(LightNode { bbox, power, kind: LightNodeKind::Leaf(indices[0]) });
+
+        // Median split along the longest axis of the bounds.
+        let axis = bbox.longest_axis();
+        let centroid = |i: usize| {
+            let range = *lights[i].bounding_box().axis_interval(axis);
+            0.5 * (range.min + range.max)
+        };
+        indices.sort_by(|&a, &b| centroid(a).total_cmp(&centroid(b)));
+
+        let mid = indices.len() / 2;
+        let (left, right) = indices.split_at_mut(mid);
+        let left = Self::build(lights, weights, left, nodes);
+        let right = Self::build(lights, weights, right, nodes);
+        nodes[node].kind = LightNodeKind::Interior(left, right);
+        node
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    fn importance(&self, node: usize, p: &Point3) -> f64 {
+        // Power over squared distance to the node's bounds, not letting the distance shrink
+        // below the size of the bounds.
+        let LightNode { bbox, power, .. } = self.bvh[node];
+        let center = Point3::new(0.5 * (bbox.x.min + bbox.x.max), 0.5 * (bbox.y.min + bbox.y.max), 0.5 * (bbox.z.min + bbox.z.max));
+        let half_diagonal = 0.5 * Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length();
+        power / (center - *p).squared_length().max(half_diagonal * half_diagonal)
+    }
+
+    fn child_probabilities(&self, left: usize, right: usize, p: &Point3) -> (f64, f64) {
+        let il = self.importance(left, p);
+        let ir = self.importance(right, p);
+        if il + ir <= 0.0 {
+            return (0.5, 0.5);
+        }
+        (il / (il + ir), ir / (il + ir))
+    }
+
+    fn select(&self, p: &Point3) -> usize {
+        match self.mode {
+            LightSampling::Bvh => {
+                let mut node = 0;
+                loop {
+                    match self.bvh[node].kind {
+                        LightNodeKind::Leaf(light) => return light,
+                        LightNodeKind::Interior(left, right) => {
+                            let (pl, _) = self.child_probabilities(left, right, p);
+                            node = if random_double(0.0, 1.0) < pl { left } else { right };
+                        }
+                    }
+                }
+            }
+            _ => {
+                let total = self.cdf.last().copied().unwrap_or(0.0);
+                let target = random_double(0.0, total);
+                self.cdf.partition_point(|&c| c <= target).min(self.lights.len() - 1)
+            }
+        }
+    }
+
+    pub fn random(&self, origin: &Point3) -> Vec3 {
+        self.lights[self.select(origin)].random(origin)
+    }
+
+    pub fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        // Sum over the lights of the chance of picking the light times its directional density.
+        match self.mode {
+            LightSampling::Bvh => {
+                if self.bvh.is_empty() {
+                    return 0.0;
+                }
+                // Only lights whose bounds the direction passes through can contribute.
+                let ray = Ray::new(*origin, *direction, 0.0);
+                let mut sum = 0.0;
+                // Called at every bounce, so the walk keeps its stack in an array.
+                let mut stack = [(0, 1.0); MAX_TREE_DEPTH + 1];
+                let mut len = 1;
+                while len > 0 {
+                    len -= 1;
+                    let (node, prob) = stack[len];
+                    if !self.bvh[node].bbox.hit(&ray, Interval::with_values(0.001, f64::INFINITY)) {
+                        continue;
+                    }
+                    match self.bvh[node].kind {
+                        LightNodeKind::Leaf(light) => sum += prob * self.lights[light].pdf_value(origin, direction),
+                        LightNodeKind::Interior(left, right) => {
+                            let (pl, pr) = self.child_probabilities(left, right, origin);
+                            stack[len] = (left, prob * pl);
+                            stack[len + 1] = (right, prob * pr);
+                            len += 2;
+                        }
+                    }
+                }
+                sum
+            }
+            _ => {
+                let total = match self.cdf.last() {
+                    Some(&total) if total > 0.0 => total,
+                    _ => return 0.0,
+                };
+                let mut previous = 0.0;
+                let mut sum = 0.0;
+                for (light, &c) in self.lights.iter().zip(&self.cdf) {
+                    sum += (c - previous) / total * light.pdf_value(origin, direction);
+                    previous = c;
+                }
+                sum
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn selection_probability(&self, light: usize, p: &Point3) -> f64 {
+        match self.mode {
+            LightSampling::Bvh => {
+                let mut stack = vec![(0, 1.0)];
+                while let Some((node, prob)) = stack.pop() {
+                    match self.bvh[node].kind {
+                        LightNodeKind::Leaf(index) if index == light => return prob,
+                        LightNodeKind::Leaf(_) => {}
+                        LightNodeKind::Interior(left, right) => {
+                            let (pl, pr) = self.child_probabilities(left, right, p);
+                            stack.push((left, prob * pl));
+                            stack.push((right, prob * pr));
+                        }
+                    }
+                }
+                0.0
+            }
+            _ => {
+                let previous = if light == 0 { 0.0 } else { self.cdf[light - 1] };
+                (self.cdf[light] - previous) / self.cdf[self.cdf.len() - 1]
+            }
+        }
+    }
+}
+
+// Starting points for paths traced out of the lights rather than towards them: an emitter picked in
+// proportion to its power, a point spread uniformly over its surface and a cosine-weighted direction.
+pub struct EmissionSampler {
+    lights: Vec<Arc<dyn Hittable + Send + Sync>>, // Emitters with positive power that can sample their surface
+    materials: Materials, // Of the scene the emitters belong to
+    cdf: Vec<f64>, // Running total of emitted power
+    total_power: f64,
+}
+
+pub struct EmissionSample {
+    pub rec: HitRecord, // Hit record at the sampled point, on the emitting side
+    pub probe: Ray, // Ray that arrives at the point along the normal, for evaluating the emission
+    pub normal: Vec3, // Unit outward normal
+    pub pdf_pos: f64, // Area density of the point, including the choice of light
+    pub direction: Vec3, // Unit direction the light leaves in
+    pub pdf_dir: f64, // Solid angle density of direction
+}
+
+impl EmissionSample {
+    pub fn p(&self) -> Point3 {
+        self.rec.p
+    }
+
+    pub fn radiance(&self, materials: &Materials) -> Color {
+        // Light leaving the point along direction.
+        let mat = materials.get(self.rec.mat.unwrap());
+        mat.emitted(&self.probe, &self.rec, self.rec.u, self.rec.v, &self.rec.p)
+    }
+}
+
+impl EmissionSampler {
+    pub fn new(emitters: &HittableList, materials: &Materials) -> Self {
+        let mut lights = Vec::new();
+        let mut cdf = Vec::new();
+        let mut total_power = 0.0;
+        for emitter in &emitters.objects {
+            let power = emitter.emitted_power(materials);
+            if power > 0.0 {
+                total_power += power;
+                lights.push(emitter.clone());
+                cdf.push(total_power);
+            }
+        }
+        EmissionSampler { lights, materials: materials.clone(), cdf, total_power }
+    }
+
+    pub fn origin_pdf(&self, rec: &HitRecord) -> f64 {
+        // Lights are picked in proportion to their power, pi * area * radiance, and sampled uniformly
+        // by area, so the area density of a point is pi * radiance / total power.
+        if self.total_power <= 0.0 {
+            return 0.0;
+        }
+        let radiance = match rec.mat {
+            Some(mat) => self.materials.get(mat).emission_estimate(),
+            None => return 0.0,
+        };
+        PI * (radiance.x + radiance.y + radiance.z) / 3.0 / self.total_power
+    }
+
+    pub fn sample(&self, time: f64) -> Option<EmissionSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let target = random_double(0.0, self.total_power);
+        let light = &self.lights[self.cdf.partition_point(|&c| c <= target).min(self.lights.len() - 1)];
+        let (p, normal) = light.sample_surface()?;
+
+        // Hit the light head on at the sampled point for its texture coordinates and material.
+        let probe = Ray::new(p + PROBE_OFFSET * normal, -1.0 * normal, time);
+        let mut rec = HitRecord::default();
+        if !light.hit(&probe, Interval::with_values(0.0, 2.0 * PROBE_OFFSET), &mut rec) {
+            return None;
+        }
+        let pdf_pos = self.origin_pdf(&rec);
+
+        // Diffuse emission, so leave in a cosine-weighted direction.
+        let direction = ONB::build_from_w(&normal).local_vec3(&Vec3::random_cosine_direction());
+        let pdf_dir = direction.dot(normal) / PI;
+        if pdf_pos <= 0.0 || pdf_dir <= 0.0 {
+            return None;
+        }
+
+        Some(EmissionSample { rec, probe, normal, pdf_pos, direction, pdf_dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use crate::sphere::Sphere;
+
+    fn light(materials: &mut Materials, center: Point3, radiance: f64) -> Arc<dyn Hittable + Send + Sync> {
+        let mat = Some(materials.add(DiffuseLight::with_color(Vec3::new(radiance, radiance, radiance))));
+        Arc::new(Sphere::new(center, 0.5, mat))
+    }
+
+    #[test]
+    fn test_power_weights() {
+        let mut materials = Materials::new();
+        let mut lights = HittableList::new();
+        lights.add(light(&mut materials, Point3::new(-2.0, 5.0, 0.0), 1.0));
+        lights.add(light(&mut materials, Point3::new(2.0, 5.0, 0.0), 3.0));
+        // A non-emitting target gets the mean power of the lights.
+        lights.add(Arc::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5, None)));
+
+        let sampler = LightSampler::new(lights, &materials, LightSampling::Power);
+        let p = Point3::zero();
+        assert!((sampler.selection_probability(0, &p) - 1.0 / 6.0).abs() < 1e-9);
+        assert!((sampler.selection_probability(1, &p) - 3.0 / 6.0).abs() < 1e-9);
+        assert!((sampler.selection_probability(2, &p) - 2.0 / 6.0).abs() < 1e-9);
+
+        let n = 20000;
+        let picked = (0..n).filter(|_| sampler.select(&p) == 1).count();
+        assert!((picked as f64 / n as f64 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_bvh_prefers_near_lights() {
+        let mut materials = Materials::new();
+        let mut lights = HittableList::new();
+        for i in 0..100 {
+            lights.add(light(&mut materials, Point3::new(i as f64 * 2.0, 3.0, 0.0), 1.0));
+        }
+        let sampler = LightSampler::new(lights, &materials, LightSampling::Bvh);
+
+        let p = Point3::new(0.0, 0.0, 0.0);
+        let total: f64 = (0..100).map(|i| sampler.selection_probability(i, &p)).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(sampler.selection_probability(0, &p) > 10.0 * sampler.selection_probability(99, &p));
+    }
+
+    #[test]
+    fn test_bvh_pdf_matches_selection() {
+        // Looking straight at one light, only that light's density counts.
+        let mut materials = Materials::new();
+        let mut lights = HittableList::new();
+        for i in 0..8 {
+            lights.add(light(&mut materials, Point3::new(i as f64 * 3.0, 4.0, 0.0), 1.0 + i as f64));
+        }
+        let sampler = LightSampler::new(lights.clone(), &materials, LightSampling::Bvh);
+
+        let p = Point3::new(1.0, 0.0, 0.5);
+        let direction = Point3::new(6.0, 4.0, 0.0) - p;
+        let expected = sampler.selection_probability(2, &p) * lights.objects[2].pdf_value(&p, &direction);
+        assert!((sampler.pdf_value(&p, &direction) - expected).abs() < 1e-12);
+    }
+}
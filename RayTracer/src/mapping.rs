@@ -0,0 +1,354 @@
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::hit::{HitRecord, UvFootprint};
+use crate::sphere::Sphere;
+use crate::texture::Texture;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Affine map of texture coordinates. The builder steps apply in the order they are called.
+#[derive(Clone, Copy)]
+pub struct Transform2 {
+    m: [[f64; 2]; 2],
+    offset: (f64, f64),
+}
+
+impl Transform2 {
+    pub fn new() -> Self {
+        Transform2 { m: [[1.0, 0.0], [0.0, 1.0]], offset: (0.0, 0.0) }
+    }
+
+    pub fn with_scale(self, su: f64, sv: f64) -> Self {
+        self.then([[su, 0.0], [0.0, sv]])
+    }
+
+    pub fn with_rotation(self, degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        self.then([[cos, -sin], [sin, cos]])
+    }
+
+    pub fn with_offset(mut self, du: f64, dv: f64) -> Self {
+        self.offset = (self.offset.0 + du, self.offset.1 + dv);
+        self
+    }
+
+    fn then(self, a: [[f64; 2]; 2]) -> Self {
+        let mul = |r: usize, c: usize| a[r][0] * self.m[0][c] + a[r][1] * self.m[1][c];
+        Transform2 {
+            m: [[mul(0, 0), mul(0, 1)], [mul(1, 0), mul(1, 1)]],
+            offset: (
+                a[0][0] * self.offset.0 + a[0][1] * self.offset.1,
+                a[1][0] * self.offset.0 + a[1][1] * self.offset.1,
+            ),
+        }
+    }
+
+    pub fn apply_vector(&self, du: f64, dv: f64) -> (f64, f64) {
+        (self.m[0][0] * du + self.m[0][1] * dv, self.m[1][0] * du + self.m[1][1] * dv)
+    }
+
+    pub fn apply(&self, u: f64, v: f64) -> (f64, f64) {
+        let (u, v) = self.apply_vector(u, v);
+        (u + self.offset.0, v + self.offset.1)
+    }
+}
+
+impl Default for Transform2 {
+    fn default() -> Self {
+        Transform2::new()
+    }
+}
+
+// Affine map from world space into a texture's own space. The builder steps apply in the order
+// they are called, so scaling by 2 makes the pattern repeat twice as often.
+#[derive(Clone, Copy)]
+pub struct Transform3 {
+    rows: [Vec3; 3],
+    offset: Vec3,
+}
+
+impl Transform3 {
+    pub fn new() -> Self {
+        Transform3 {
+            rows: [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)],
+            offset: Vec3::zero(),
+        }
+    }
+
+    pub fn with_scale(self, scale: Vec3) -> Self {
+        self.then([Vec3::new(scale.x, 0.0, 0.0), Vec3::new(0.0, scale.y, 0.0), Vec3::new(0.0, 0.0, scale.z)])
+    }
+
+    pub fn with_rotation(self, axis: Vec3, degrees: f64) -> Self {
+        // Rodrigues' rotation matrix about the unit axis.
+        let k = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        self.then([
+            Vec3::new(cos + t * k.x * k.x, t * k.x * k.y - sin * k.z, t * k.x * k.z + sin * k.y),
+            Vec3::new(t * k.y * k.x + sin * k.z, cos + t * k.y * k.y, t * k.y * k.z - sin * k.x),
+            Vec3::new(t * k.z * k.x - sin * k.y, t * k.z * k.y + sin * k.x, cos + t * k.z * k.z),
+        ])
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset += offset;
+        self
+    }
+
+    fn then(self, a: [Vec3; 3]) -> Self {
+        let columns = [
+            Vec3::new(self.rows[0].x, self.rows[1].x, self.rows[2].x),
+            Vec3::new(self.rows[0].y, self.rows[1].y, self.rows[2].y),
+            Vec3::new(self.rows[0].z, self.rows[1].z, self.rows[2].z),
+        ];
+        let row = |r: usize| Vec3::new(a[r].dot(columns[0]), a[r].dot(columns[1]), a[r].dot(columns[2]));
+        Transform3 {
+            rows: [row(0), row(1), row(2)],
+            offset: Vec3::new(a[0].dot(self.offset), a[1].dot(self.offset), a[2].dot(self.offset)),
+        }
+    }
+
+    pub fn apply_vector(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.rows[0].dot(v), self.rows[1].dot(v), self.rows[2].dot(v))
+    }
+
+    pub fn apply(&self, p: Point3) -> Point3 {
+        self.apply_vector(p) + self.offset
+    }
+
+    pub fn apply_normal(&self, n: Vec3) -> Vec3 {
+        // Normals go through the inverse transpose, so that they stay perpendicular to the surface
+        // under non-uniform scale and shear. The cofactor matrix is that times the determinant;
+        // the result is left unnormalized.
+        let [r0, r1, r2] = self.rows;
+        let cofactors = [r1.cross(r2), r2.cross(r0), r0.cross(r1)];
+        let sign = r0.dot(cofactors[0]).signum();
+        sign * Vec3::new(cofactors[0].dot(n), cofactors[1].dot(n), cofactors[2].dot(n))
+    }
+}
+
+impl Default for Transform3 {
+    fn default() -> Self {
+        Transform3::new()
+    }
+}
+
+// Where a texture's (u, v) comes from. The projections work in the texture's own space.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Projection {
+    Uv, // The primitive's own (u, v)
+    Planar, // (x, y), looking down the z axis
+    Cylindrical, // Angle around the y axis and height along it
+    Spherical, // Same layout as Sphere::get_sphere_uv
+}
+
+impl Projection {
+    fn map(&self, u: f64, v: f64, p: Point3) -> (f64, f64) {
+        match self {
+            Projection::Uv => (u, v),
+            Projection::Planar => (p.x, p.y),
+            Projection::Cylindrical => ((-p.z).atan2(p.x) / (2.0 * PI) + 0.5, p.y),
+            Projection::Spherical => {
+                if p.near_zero() {
+                    (0.0, 0.0)
+                } else {
+                    Sphere::get_sphere_uv(p.unit())
+                }
+            }
+        }
+    }
+}
+
+// Looks up another texture through a projection and 3D/2D transforms, so the texture can be
+// positioned independently of the geometry it is applied to.
+#[derive(Clone)]
+pub struct MappedTexture {
+    tex: Arc<dyn Texture + Send + Sync>,
+    projection: Projection,
+    transform: Transform3, // World space to texture space, also seen by solid textures
+    uv_transform: Transform2, // Applied to the projected (u, v)
+}
+
+impl MappedTexture {
+    pub fn new(tex: Arc<dyn Texture + Send + Sync>, projection: Projection) -> Self {
+        MappedTexture {
+            tex,
+            projection,
+            transform: Transform3::new(),
+            uv_transform: Transform2::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform3) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_uv_transform(mut self, uv_transform: Transform2) -> Self {
+        self.uv_transform = uv_transform;
+        self
+    }
+
+    fn map(&self, u: f64, v: f64, p: &Point3) -> (f64, f64, Point3) {
+        let q = self.transform.apply(*p);
+        let (u, v) = self.projection.map(u, v, q);
+        let (u, v) = self.uv_transform.apply(u, v);
+        (u, v, q)
+    }
+}
+
+impl Texture for MappedTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        let (u, v, q) = self.map(u, v, p);
+        self.tex.value(u, v, &q)
+    }
+
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        let mut local = rec.clone();
+        (local.u, local.v, local.p) = self.map(rec.u, rec.v, &rec.p);
+
+        // The surface footprint carries over through a uv transform; projections fall back to
+        // a point lookup.
+        local.footprint = if self.projection == Projection::Uv {
+            let fp = rec.footprint;
+            UvFootprint {
+                major: self.uv_transform.apply_vector(fp.major.0, fp.major.1),
+                minor: self.uv_transform.apply_vector(fp.minor.0, fp.minor.1),
+            }
+        } else {
+            UvFootprint::default()
+        };
+
+        self.tex.value_filtered(&local)
+    }
+}
+
+// Projects a texture along all three axes and blends by the surface normal, for surfaces
+// without usable (u, v).
+#[derive(Clone)]
+pub struct TriplanarTexture {
+    tex: Arc<dyn Texture + Send + Sync>,
+    sharpness: f64, // Exponent on the blend weights, higher gives crisper seams
+    transform: Transform3,
+}
+
+impl TriplanarTexture {
+    pub fn new(tex: Arc<dyn Texture + Send + Sync>) -> Self {
+        TriplanarTexture { tex, sharpness: 4.0, transform: Transform3::new() }
+    }
+
+    pub fn with_sharpness(mut self, sharpness: f64) -> Self {
+        self.sharpness = sharpness.max(0.0);
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform3) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    fn blend(&self, p: &Point3, normal: Option<Vec3>) -> Color {
+        let q = self.transform.apply(*p);
+        // Without a normal (plain value lookups) the three projections are mixed evenly.
+        let (wx, wy, wz) = match normal.map(|n| self.transform.apply_normal(n)) {
+            Some(n) if !n.near_zero() => {
+                let n = n.unit();
+                (n.x.abs().powf(self.sharpness), n.y.abs().powf(self.sharpness), n.z.abs().powf(self.sharpness))
+            }
+            _ => (1.0, 1.0, 1.0),
+        };
+        let total = wx + wy + wz;
+
+        let mut color = Color::zero();
+        if wx > 0.0 {
+            color += (wx / total) * self.tex.value(q.z, q.y, &q);
+        }
+        if wy > 0.0 {
+            color += (wy / total) * self.tex.value(q.x, q.z, &q);
+        }
+        if wz > 0.0 {
+            color += (wz / total) * self.tex.value(q.x, q.y, &q);
+        }
+        color
+    }
+}
+
+impl Texture for TriplanarTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        self.blend(p, None)
+    }
+
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        self.blend(&rec.p, Some(rec.normal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::UvCheckerTexture;
+
+    // Shows the texture coordinates it is looked up with as a colour.
+    struct UvProbe;
+
+    impl Texture for UvProbe {
+        fn value(&self, u: f64, v: f64, _p: &Vec3) -> Color {
+            Color::new(u, v, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_transform2_order() {
+        // Scale, then rotate a quarter turn, then shift.
+        let t = Transform2::new().with_scale(2.0, 1.0).with_rotation(90.0).with_offset(0.5, 0.0);
+        let (u, v) = t.apply(1.0, 0.0);
+        assert!((u - 0.5).abs() < 1e-12);
+        assert!((v - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_transform3_rotation() {
+        let t = Transform3::new().with_offset(Vec3::new(1.0, 0.0, 0.0)).with_rotation(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let p = t.apply(Point3::zero());
+        assert!((p - Point3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_spherical_projection_matches_sphere() {
+        let tex = MappedTexture::new(Arc::new(UvProbe), Projection::Spherical)
+            .with_transform(Transform3::new().with_offset(Vec3::new(0.0, -2.0, 0.0)));
+        let c = tex.value(0.9, 0.9, &Point3::new(0.0, 2.0, 3.0));
+        assert!((c - Color::new(0.25, 0.5, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_triplanar_follows_normal() {
+        let tex = TriplanarTexture::new(Arc::new(UvProbe));
+        let rec = HitRecord { p: Point3::new(0.1, 0.2, 0.3), normal: Vec3::new(0.0, 1.0, 0.0), ..HitRecord::default() };
+        assert!((tex.value_filtered(&rec) - Color::new(0.1, 0.3, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_triplanar_normal_under_non_uniform_scale() {
+        // Stretching x by 4 turns a surface facing (1, 1, 0) to face (0.25, 1, 0) in texture
+        // space, so the y projection dominates the blend.
+        let tex = TriplanarTexture::new(Arc::new(UvProbe) as Arc<dyn Texture + Send + Sync>)
+            .with_transform(Transform3::new().with_scale(Vec3::new(4.0, 1.0, 1.0)));
+        let rec = HitRecord { p: Point3::new(0.1, 0.2, 0.3), normal: Vec3::new(1.0, 1.0, 0.0).unit(), ..HitRecord::default() };
+        let wx = 0.25_f64.powi(4);
+        let expected = (wx * Color::new(0.3, 0.2, 0.0) + Color::new(0.4, 0.3, 0.0)) / (wx + 1.0);
+        assert!((tex.value_filtered(&rec) - expected).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_uv_checker() {
+        let checker = UvCheckerTexture::from_color((4.0, 2.0), Color::zero(), Color::new(1.0, 1.0, 1.0));
+        let p = Point3::zero();
+        assert_eq!(checker.value(0.1, 0.1, &p).x, 0.0);
+        assert_eq!(checker.value(0.3, 0.1, &p).x, 1.0);
+        assert_eq!(checker.value(0.3, 0.6, &p).x, 0.0);
+        assert_eq!(checker.value(-0.1, 0.1, &p).x, 1.0);
+    }
+}
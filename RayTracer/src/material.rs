@@ -8,12 +8,12 @@ use crate::rtweekend::random_double;
 use crate::texture::{Texture, SolidColor};
 use std::sync::Arc;
 // use crate::onb::ONB;
-use crate::pdf::{Pdf, CosinePdf, SpherePdf};
+use crate::pdf::{CosinePdf, ScatterPdf, SpherePdf};
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct ScatterRecord {
     pub attenuation: Color,
-    pub pdf_ptr: Arc<dyn Pdf + Send + Sync>,
+    pub pdf: ScatterPdf, // Held by value, so scattering allocates nothing
     pub skip_pdf: bool,
     pub skip_pdf_ray: Ray,
 }
@@ -22,13 +22,39 @@ impl Default for ScatterRecord {
     fn default() -> Self {
         ScatterRecord {
             attenuation: Color::zero(),
-            pdf_ptr: Arc::new(SpherePdf::new()),
+            pdf: ScatterPdf::Sphere(SpherePdf::new()),
             skip_pdf: false,
             skip_pdf_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
         }
     }
 }
 
+// Index of a material in the Materials of a scene. Objects and hit records carry this instead of
+// the material itself, so that recording a hit copies a number rather than a reference count.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MaterialId(u32);
+
+// Every material of a scene, looked up by the MaterialId its objects were given when it was added.
+#[derive(Clone, Default)]
+pub struct Materials {
+    table: Vec<Arc<dyn Material + Send + Sync>>,
+}
+
+impl Materials {
+    pub fn new() -> Self {
+        Materials { table: Vec::new() }
+    }
+
+    pub fn add(&mut self, mat: impl Material + Send + Sync) -> MaterialId {
+        self.table.push(Arc::new(mat));
+        MaterialId((self.table.len() - 1) as u32)
+    }
+
+    pub fn get(&self, id: MaterialId) -> &(dyn Material + Send + Sync) {
+        self.table[id.0 as usize].as_ref()
+    }
+}
+
 pub trait Material: Any {
     fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _scatter_rec: &mut ScatterRecord) -> bool {
         false
@@ -40,40 +66,45 @@ pub trait Material: Any {
     fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
         0.0
     }
+    // Whether emitted can return anything, so that surfaces using it are sampled as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+    // Rough radiance leaving the surface, used to weigh lights against each other.
+    fn emission_estimate(&self) -> Color {
+        Color::zero()
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
     tex: Arc<dyn Texture + Send + Sync>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
         Lambertian {
-            albedo,
             tex: Arc::new(SolidColor::new(albedo)),
         }
     }
     pub fn with_texture(tex: Arc<dyn Texture + Send + Sync>) -> Self {
-        Lambertian {
-            albedo: Color::new(0.0, 0.0, 0.0), // Placeholder value, replace with desired albedo
-            tex,
-        }
+        Lambertian { tex }
     }
 }
 
 impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord, scatter_rec: &mut ScatterRecord) -> bool {
-        scatter_rec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
-        scatter_rec.pdf_ptr = Arc::new(CosinePdf::new(rec.normal));
+        scatter_rec.attenuation = self.tex.value_filtered(rec);
+        scatter_rec.pdf = ScatterPdf::Cosine(CosinePdf::new(rec.normal));
         scatter_rec.skip_pdf = false;
         true
     }
     fn as_any(&self) -> &dyn Any {
         self
     }
-    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
-        1.0 / (2.0 * std::f64::consts::PI)
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        // Cosine weighted over the hemisphere around the normal, matching the directions scatter draws.
+        let cos_theta = rec.normal.dot(scattered.direction().unit());
+        if cos_theta < 0.0 { 0.0 } else { cos_theta / std::f64::consts::PI }
     }
 }
 
@@ -134,13 +165,11 @@ impl Material for Dielectric {
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction: Vec3;
-
-        if cannot_refract || self.reflectance(cos_theta) > random_double(0.0, 1.0) {
-            direction = unit_direction.reflect(rec.normal);
+        let direction = if cannot_refract || self.reflectance(cos_theta) > random_double(0.0, 1.0) {
+            unit_direction.reflect(rec.normal)
         } else {
-            direction = unit_direction.refract(rec.normal, ri);
-        }
+            unit_direction.refract(rec.normal, ri)
+        };
 
         scatter_rec.skip_pdf_ray = Ray::new(rec.p, direction, r_in.time());
         true
@@ -173,6 +202,12 @@ impl Material for DiffuseLight {
         }
         self.tex.value(u, v, p)
     }
+    fn is_emissive(&self) -> bool {
+        true
+    }
+    fn emission_estimate(&self) -> Color {
+        self.tex.value(0.5, 0.5, &Vec3::zero())
+    }
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -199,7 +234,7 @@ impl Isotropic {
 impl Material for Isotropic {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord, scatter_rec: &mut ScatterRecord) -> bool {
         scatter_rec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
-        scatter_rec.pdf_ptr = Arc::new(SpherePdf::new());
+        scatter_rec.pdf = ScatterPdf::Sphere(SpherePdf::new());
         scatter_rec.skip_pdf = false;
         true
     }
@@ -211,3 +246,45 @@ impl Material for Isotropic {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chi_square::{chi_square_test, integral};
+    use crate::pdf::Pdf;
+    use crate::rtweekend::seed_random;
+
+    // The directions a material scatters into, with the density it reports for them.
+    struct ScatteringPdf<'a> {
+        mat: &'a dyn Material,
+        r_in: Ray,
+        rec: HitRecord,
+        scatter_rec: ScatterRecord,
+    }
+
+    impl Pdf for ScatteringPdf<'_> {
+        fn value(&self, direction: &Vec3) -> f64 {
+            self.mat.scattering_pdf(&self.r_in, &self.rec, &Ray::new(self.rec.p, *direction, 0.0))
+        }
+        fn generate(&self) -> Vec3 {
+            self.scatter_rec.pdf.generate()
+        }
+    }
+
+    #[test]
+    fn test_lambertian_scattering_pdf() {
+        seed_random(466);
+        let mat = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let rec = HitRecord { normal: Vec3::new(0.6, 0.0, 0.8), ..HitRecord::default() };
+        let r_in = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut scatter_rec = ScatterRecord::default();
+        assert!(mat.scatter(&r_in, &rec, &mut scatter_rec));
+
+        let pdf = ScatteringPdf { mat: &mat, r_in, rec, scatter_rec };
+        let total = integral(&pdf);
+        assert!((total - 1.0).abs() < 0.01, "Lambertian integrates to {}", total);
+        if let Err(why) = chi_square_test(&pdf, 200_000) {
+            panic!("Lambertian scatters unlike its scattering_pdf: {}", why);
+        }
+    }
+}
@@ -1,9 +1,16 @@
 use crate::vec3::Vec3;
 
+#[derive(Clone, Copy)]
 pub struct ONB {
     axis: [Vec3; 3],
 }
 
+impl Default for ONB {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ONB {
     pub fn new() -> Self {
         Self {
@@ -3,15 +3,23 @@ use crate::vec3::Vec3;
 use crate::hit_list::HittableList;
 use crate::hit::{Hittable};
 type Point3 = Vec3;
-use std::sync::Arc;
+use crate::light_sampler::LightSampler;
+use crate::rtweekend::random_double;
 
 pub trait Pdf {
     fn value(&self, direction: &Vec3) -> f64;
     fn generate(&self) -> Vec3;
 }
 
+#[derive(Clone, Copy)]
 pub struct SpherePdf;
 
+impl Default for SpherePdf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SpherePdf {
     pub fn new() -> Self {
         SpherePdf
@@ -19,7 +27,7 @@ impl SpherePdf {
 }
 
 impl Pdf for SpherePdf {
-    fn value(&self, direction: &Vec3) -> f64 {
+    fn value(&self, _direction: &Vec3) -> f64 {
         1.0 / (4.0 * std::f64::consts::PI)
     }
 
@@ -28,6 +36,7 @@ impl Pdf for SpherePdf {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct CosinePdf {
     uvw: ONB,
 }
@@ -50,21 +59,47 @@ impl Pdf for CosinePdf {
     }
 }
 
-#[derive(Clone)]
-pub struct HittablePdf {
-    objects: HittableList,
+// The distributions materials scatter by, as a value a ScatterRecord can hold.
+#[derive(Clone, Copy)]
+pub enum ScatterPdf {
+    Sphere(SpherePdf),
+    Cosine(CosinePdf),
+}
+
+impl Pdf for ScatterPdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        match self {
+            ScatterPdf::Sphere(pdf) => pdf.value(direction),
+            ScatterPdf::Cosine(pdf) => pdf.value(direction),
+        }
+    }
+
+    fn generate(&self) -> Vec3 {
+        match self {
+            ScatterPdf::Sphere(pdf) => pdf.generate(),
+            ScatterPdf::Cosine(pdf) => pdf.generate(),
+        }
+    }
+}
+
+// The PDFs below borrow what they sample rather than own it, so that they can be made on the stack
+// at every bounce.
+
+#[derive(Clone, Copy)]
+pub struct HittablePdf<'a> {
+    objects: &'a HittableList,
     origin: Point3,
 }
 
-impl HittablePdf {
-    pub fn new(objects: HittableList, origin: Point3) -> Self {
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a HittableList, origin: Point3) -> Self {
         HittablePdf { objects, origin }
     }
 }
 
-impl Pdf for HittablePdf {
+impl Pdf for HittablePdf<'_> {
     fn value(&self, direction: &Vec3) -> f64 {
-        self.objects.pdf_value(&self.origin, &direction)
+        self.objects.pdf_value(&self.origin, direction)
     }
 
     fn generate(&self) -> Vec3 {
@@ -72,27 +107,125 @@ impl Pdf for HittablePdf {
     }
 }
 
-pub struct MixturePdf {
-    p0: Arc<dyn Pdf + Sync + Send>,
-    p1: Arc<dyn Pdf + Sync + Send>,
+// Density of the directions a light sampler produces from one origin.
+#[derive(Clone, Copy)]
+pub struct LightPdf<'a> {
+    lights: &'a LightSampler,
+    origin: Point3,
+}
+
+impl<'a> LightPdf<'a> {
+    pub fn new(lights: &'a LightSampler, origin: Point3) -> Self {
+        LightPdf { lights, origin }
+    }
 }
 
-impl MixturePdf {
-    pub fn new(p0: Arc<dyn Pdf + Sync + Send>, p1: Arc<dyn Pdf + Sync + Send>) -> Self {
+impl Pdf for LightPdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.lights.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.lights.random(&self.origin)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MixturePdf<'a> {
+    p0: &'a dyn Pdf,
+    p1: &'a dyn Pdf,
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
         MixturePdf { p0, p1 }
     }
 }
 
-impl Pdf for MixturePdf {
+impl Pdf for MixturePdf<'_> {
     fn value(&self, direction: &Vec3) -> f64 {
         0.5 * self.p0.value(direction) + 0.5 * self.p1.value(direction)
     }
 
     fn generate(&self) -> Vec3 {
-        if rand::random::<f64>() < 0.5 {
+        if random_double(0.0, 1.0) < 0.5 {
             self.p0.generate()
         } else {
-            self.p0.generate()
+            self.p1.generate()
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chi_square::{chi_square_test, integral};
+    use crate::light_sampler::LightSampling;
+    use crate::material::{DiffuseLight, Materials};
+    use crate::quad::Quad;
+    use crate::rtweekend::seed_random;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    const SAMPLES: usize = 200_000;
+
+    fn check(name: &str, pdf: &dyn Pdf) {
+        let total = integral(pdf);
+        assert!((total - 1.0).abs() < 0.01, "{} integrates to {}", name, total);
+        if let Err(why) = chi_square_test(pdf, SAMPLES) {
+            panic!("{} draws directions unlike its value: {}", name, why);
+        }
+    }
+
+    fn lights() -> (HittableList, Materials) {
+        // A quad and a sphere of different brightness, both seen from around the origin.
+        let mut materials = Materials::new();
+        let mut light = |radiance| Some(materials.add(DiffuseLight::with_color(Vec3::new(radiance, radiance, radiance))));
+        let mut lights = HittableList::new();
+        lights.add(Arc::new(Quad::new(Point3::new(-1.0, 1.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.5, 1.5), light(4.0))));
+        lights.add(Arc::new(Sphere::new(Point3::new(1.0, -1.0, 2.0), 1.0, light(1.0))));
+        (lights, materials)
+    }
+
+    #[test]
+    fn test_sphere_pdf() {
+        seed_random(461);
+        check("SpherePdf", &SpherePdf::new());
+    }
+
+    #[test]
+    fn test_cosine_pdf() {
+        seed_random(462);
+        check("CosinePdf", &CosinePdf::new(Vec3::new(0.3, -0.5, 0.8)));
+    }
+
+    #[test]
+    fn test_quad_and_sphere_light_pdfs() {
+        seed_random(463);
+        let (lights, _) = lights();
+        let quad = HittableList::hittable_list(lights.objects[0].clone());
+        let sphere = HittableList::hittable_list(lights.objects[1].clone());
+        check("Quad", &HittablePdf::new(&quad, Point3::new(0.2, 0.1, 0.3)));
+        check("Sphere", &HittablePdf::new(&sphere, Point3::new(0.2, 0.1, 0.3)));
+        check("HittablePdf", &HittablePdf::new(&lights, Point3::new(0.2, 0.1, 0.3)));
+    }
+
+    #[test]
+    fn test_light_pdfs() {
+        seed_random(464);
+        for mode in [LightSampling::Uniform, LightSampling::Power, LightSampling::Bvh] {
+            let (lights, materials) = lights();
+            let sampler = LightSampler::new(lights, &materials, mode);
+            check(&format!("LightPdf with {:?}", mode), &LightPdf::new(&sampler, Point3::new(0.2, 0.1, 0.3)));
+        }
+    }
+
+    #[test]
+    fn test_mixture_pdf() {
+        seed_random(465);
+        let (lights, _) = lights();
+        let cosine = CosinePdf::new(Vec3::new(0.0, 1.0, 0.0));
+        let light = HittablePdf::new(&lights, Point3::new(0.2, 0.1, 0.3));
+        check("MixturePdf", &MixturePdf::new(&light, &cosine));
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
-use crate::rtweekend::random_int;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use crate::vec3::Vec3;
+use crate::rtweekend::random_u64;
 type Point3 = Vec3;
 
 #[derive(Clone)]
@@ -10,18 +12,34 @@ pub struct Perlin {
     perm_z: Vec<i32>,
 }
 
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Perlin {
     const POINT_COUNT: usize = 256;
 
     pub fn new() -> Self {
+        Self::with_seed(random_u64())
+    }
+
+    // Same seed, same noise, so procedural textures come out identical between runs.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng<R: Rng>(rng: &mut R) -> Self {
         let mut randvec = vec![Vec3::default(); Self::POINT_COUNT];
-        for i in 0..Self::POINT_COUNT {
-            randvec[i] = (Vec3::random(-1.0, 1.0)).unit();
+        for v in randvec.iter_mut() {
+            let random = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            *v = random.unit();
         }
 
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(rng);
+        let perm_y = Self::perlin_generate_perm(rng);
+        let perm_z = Self::perlin_generate_perm(rng);
 
         Self {
             randvec,
@@ -31,6 +49,7 @@ impl Perlin {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, p: &Point3) -> f64 {
         let u = p.x() - p.x().floor();
         let v = p.y() - p.y().floor();
@@ -70,43 +89,54 @@ impl Perlin {
         accum.abs()
     }
 
-    fn perlin_generate_perm() -> Vec<i32> {
-        let mut p = vec![0; Self::POINT_COUNT];
+    pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        // Fractal Brownian motion, roughly in [-1, 1].
+        self.octave_sum(p, octaves, lacunarity, gain, |n| n)
+    }
+
+    pub fn turbulence(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        // Billowy sum of absolute octaves, in [0, 1].
+        self.octave_sum(p, octaves, lacunarity, gain, f64::abs)
+    }
+
+    pub fn ridged(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        // Sharp crests where the noise crosses zero, in [0, 1].
+        self.octave_sum(p, octaves, lacunarity, gain, |n| (1.0 - n.abs()) * (1.0 - n.abs()))
+    }
+
+    fn octave_sum(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64, shape: impl Fn(f64) -> f64) -> f64 {
+        // Weighted sum of shaped octaves, normalised by the total weight.
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
 
-        for i in 0..Self::POINT_COUNT {
-            p[i] = i as i32;
+        for _ in 0..octaves {
+            accum += weight * shape(self.noise(&temp_p));
+            total += weight;
+            weight *= gain;
+            temp_p *= lacunarity;
         }
 
-        Self::permute(&mut p, Self::POINT_COUNT);
+        if total > 0.0 { accum / total } else { 0.0 }
+    }
+
+    fn perlin_generate_perm<R: Rng>(rng: &mut R) -> Vec<i32> {
+        let mut p: Vec<i32> = (0..Self::POINT_COUNT as i32).collect();
+
+        Self::permute(rng, &mut p, Self::POINT_COUNT);
 
         p
     }
 
-    fn permute(p: &mut [i32], n: usize) {
+    fn permute<R: Rng>(rng: &mut R, p: &mut [i32], n: usize) {
         for i in (1..n).rev() {
-            let target = random_int(0, i as i32) as usize;
-            let tmp = p[i];
-            p[i] = p[target];
-            p[target] = tmp;
+            let target = rng.gen_range(0..=i);
+            p.swap(i, target);
         }
     }
 
-    fn trilinear_interp(c: [[[f64; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
-        let mut accum = 0.0;
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    accum += (i as f64 * u + (1.0 - i as f64) * (1.0 - u))
-                        * (j as f64 * v + (1.0 - j as f64) * (1.0 - v))
-                        * (k as f64 * w + (1.0 - k as f64) * (1.0 - w))
-                        * c[i][j][k];
-                }
-            }
-        }
-
-        accum
-    }
-
+    #[allow(clippy::needless_range_loop)]
     fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
         let uu = u * u * (3.0 - 2.0 * u);
         let vv = v * v * (3.0 - 2.0 * v);
@@ -0,0 +1,309 @@
+use crate::hit::{HitRecord, Hittable};
+use crate::hit_list::HittableList;
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::interval::Interval;
+use crate::light_sampler::EmissionSampler;
+use crate::material::{Materials, ScatterRecord};
+use crate::pdf::Pdf;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub p: Point3,
+    pub direction: Vec3, // Unit direction the photon was travelling in when it landed
+    pub power: Color, // Flux carried by the photon
+}
+
+fn coordinate(p: &Point3, axis: usize) -> f64 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
+}
+
+// One of the nearest photons found so far, ordered by distance so the farthest is on top of the heap.
+struct Neighbour {
+    distance_squared: f64,
+    index: usize,
+}
+
+impl PartialEq for Neighbour {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance_squared == other.distance_squared
+    }
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+// Balanced kd-tree kept in one array: the middle photon of every range splits the rest of the range
+// along the stored axis, with the lower half before it and the upper half after.
+pub struct KdTree {
+    photons: Vec<Photon>,
+    axes: Vec<usize>, // Split axis of the node at the same index
+}
+
+impl KdTree {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axes);
+        KdTree { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.len() <= 1 {
+            return;
+        }
+
+        // Split along the axis the photons spread furthest on.
+        let mut min = photons[0].p;
+        let mut max = photons[0].p;
+        for photon in photons.iter() {
+            min = Point3::new(min.x.min(photon.p.x), min.y.min(photon.p.y), min.z.min(photon.p.z));
+            max = Point3::new(max.x.max(photon.p.x), max.y.max(photon.p.y), max.z.max(photon.p.z));
+        }
+        let extent = max - min;
+        let axis = if extent.x > extent.y && extent.x > extent.z { 0 } else if extent.y > extent.z { 1 } else { 2 };
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| coordinate(&a.p, axis).total_cmp(&coordinate(&b.p, axis)));
+        axes[mid] = axis;
+
+        let (lower, upper) = photons.split_at_mut(mid);
+        let (lower_axes, upper_axes) = axes.split_at_mut(mid);
+        Self::build(lower, lower_axes);
+        Self::build(&mut upper[1..], &mut upper_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    pub fn nearest(&self, p: &Point3, k: usize, max_distance: f64) -> Vec<(f64, &Photon)> {
+        // Up to k photons within max_distance of p, with their squared distances, nearest first.
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.search(0, self.photons.len(), p, k, max_distance * max_distance, &mut heap);
+        }
+        heap.into_sorted_vec().into_iter().map(|n| (n.distance_squared, &self.photons[n.index])).collect()
+    }
+
+    fn search(&self, lo: usize, hi: usize, p: &Point3, k: usize, max_distance_squared: f64, heap: &mut BinaryHeap<Neighbour>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+
+        let distance_squared = (photon.p - *p).squared_length();
+        if distance_squared <= max_distance_squared {
+            heap.push(Neighbour { distance_squared, index: mid });
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        // Search the side p is on first, then the other side if it can still hold nearer photons.
+        let axis = self.axes[mid];
+        let diff = coordinate(p, axis) - coordinate(&photon.p, axis);
+        let (near, far) = if diff < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.search(near.0, near.1, p, k, max_distance_squared, heap);
+
+        let bound = match heap.peek() {
+            Some(farthest) if heap.len() == k => farthest.distance_squared,
+            _ => max_distance_squared,
+        };
+        if diff * diff < bound {
+            self.search(far.0, far.1, p, k, max_distance_squared, heap);
+        }
+    }
+}
+
+// Photons that reached a diffuse surface from the lights only by way of mirrors and glass. Path
+// tracing finds these caustic paths only when a diffuse bounce happens to scatter back through the
+// specular surfaces onto a light, so they are estimated from the photon density instead.
+pub struct PhotonMap {
+    tree: KdTree,
+    nearest: usize, // Photons gathered per estimate
+    radius: f64, // Farthest a gathered photon may be
+}
+
+impl PhotonMap {
+    pub fn new(photons: Vec<Photon>, nearest: usize, radius: f64) -> Self {
+        PhotonMap { tree: KdTree::new(photons), nearest, radius }
+    }
+
+    pub fn caustics(world: &HittableList, materials: &Materials, emitters: &HittableList, photon_count: usize, max_depth: u32, nearest: usize, radius: f64) -> Self {
+        let emission = EmissionSampler::new(emitters, materials);
+        let mut photons = Vec::new();
+
+        for _ in 0..photon_count {
+            let sample = match emission.sample(random_double(0.0, 1.0)) {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            // Each photon carries its share of the light's flux, radiance * cos / (pdf_pos * pdf_dir).
+            let cosine = sample.direction.dot(sample.normal);
+            let mut power = sample.radiance(materials) * cosine / (sample.pdf_pos * sample.pdf_dir * photon_count as f64);
+            let mut r = Ray::new(sample.p(), sample.direction, sample.probe.time());
+            let mut specular = false;
+
+            for _ in 0..max_depth {
+                let mut rec = HitRecord::default();
+                if !world.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+                    break;
+                }
+                rec.apply_shading_normal();
+
+                let mat = materials.get(rec.mat.unwrap());
+                let mut srec = ScatterRecord::default();
+                if !mat.scatter(&r, &rec, &mut srec) {
+                    break;
+                }
+                if srec.skip_pdf {
+                    power = power * srec.attenuation;
+                    specular = true;
+                    r = srec.skip_pdf_ray;
+                    continue;
+                }
+
+                // Light reaching a diffuse surface directly is left to path tracing.
+                if specular {
+                    photons.push(Photon { p: rec.p, direction: r.direction().unit(), power });
+                }
+                break;
+            }
+        }
+
+        PhotonMap::new(photons, nearest, radius)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn caustic_radiance(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, materials: &Materials) -> Color {
+        // Density estimate over the nearest photons: the sum of f * flux over the disc they cover.
+        if self.is_empty() {
+            return Color::zero();
+        }
+        let neighbours = self.tree.nearest(&rec.p, self.nearest, self.radius);
+        if neighbours.is_empty() {
+            return Color::zero();
+        }
+
+        // With all k photons found, the disc reaches the farthest of them; otherwise the whole radius.
+        let radius_squared = if neighbours.len() == self.nearest {
+            neighbours[neighbours.len() - 1].0
+        } else {
+            self.radius * self.radius
+        };
+        if radius_squared <= 0.0 {
+            return Color::zero();
+        }
+
+        let mat = materials.get(rec.mat.unwrap());
+        let mut sum = Color::zero();
+        for (_, photon) in neighbours {
+            // Only photons arriving on the side being looked at.
+            let incoming = -1.0 * photon.direction;
+            let cosine = incoming.dot(rec.normal);
+            if cosine <= 0.0 || srec.pdf.value(&incoming) <= 0.0 {
+                continue;
+            }
+            let scattering_pdf = mat.scattering_pdf(r_in, rec, &Ray::new(rec.p, incoming, r_in.time()));
+            sum += srec.attenuation * scattering_pdf / cosine * photon.power;
+        }
+
+        sum / (std::f64::consts::PI * radius_squared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian};
+    use crate::quad::Quad;
+    use crate::rtweekend::seed_random;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_nearest_matches_brute_force() {
+        let photons: Vec<Photon> = (0..500)
+            .map(|_| Photon {
+                p: Point3::new(random_double(-1.0, 1.0), random_double(-1.0, 1.0), random_double(-1.0, 1.0)),
+                direction: Vec3::new(0.0, -1.0, 0.0),
+                power: Color::new(1.0, 1.0, 1.0),
+            })
+            .collect();
+        let tree = KdTree::new(photons.clone());
+
+        for _ in 0..20 {
+            let p = Point3::new(random_double(-1.0, 1.0), random_double(-1.0, 1.0), random_double(-1.0, 1.0));
+            let found = tree.nearest(&p, 10, 0.5);
+
+            let mut expected: Vec<f64> = photons.iter().map(|photon| (photon.p - p).squared_length()).filter(|&d| d <= 0.25).collect();
+            expected.sort_by(f64::total_cmp);
+            expected.truncate(10);
+
+            let found: Vec<f64> = found.iter().map(|(d, _)| *d).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_glass_sphere_focuses_photons() {
+        // A light straight above a glass ball over a floor: every stored photon went through the
+        // ball, so they land in its shadow, around the point straight below it.
+        seed_random(371);
+        let mut materials = Materials::new();
+        let light = Some(materials.add(DiffuseLight::with_color(Color::new(10.0, 10.0, 10.0))));
+        let glass = Some(materials.add(Dielectric::new(1.5)));
+        let grey = Some(materials.add(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(Point3::new(-20.0, 0.0, -20.0), Vec3::new(40.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 40.0), grey)));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 2.0, 0.0), 1.0, glass)));
+        world.add(Arc::new(Quad::new(Point3::new(-0.5, 6.0, -0.5), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), light)));
+        let mut emitters = HittableList::new();
+        world.collect_emitters(&materials, &mut emitters);
+
+        let map = PhotonMap::caustics(&world, &materials, &emitters, 20000, 10, 50, 0.5);
+        assert!(map.len() > 100);
+        for photon in &map.tree.photons {
+            assert!(photon.p.y.abs() < 1e-6);
+        }
+
+        // The focus below the ball is much brighter than the floor beside it.
+        let below = map.tree.nearest(&Point3::zero(), 50, 0.5).len();
+        let beside = map.tree.nearest(&Point3::new(3.0, 0.0, 0.0), 50, 0.5).len();
+        assert!(below > 10 * beside.max(1), "{} vs {}", below, beside);
+    }
+}
@@ -0,0 +1,194 @@
+// Live view of a render in progress, served over HTTP for a browser on the same machine:
+//
+//     /            a page showing the image, reloading it every few seconds
+//     /image.png   the image as of the last finished pass
+//     /stats       render statistics as JSON
+//
+// Only the little of HTTP/1.0 a browser needs is spoken, one request per connection.
+
+use crate::accumulation::Accumulation;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Longest a browser may take to send its request or to take the response, after which its
+// connection is dropped so the next one gets served.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PAGE: &str = "<!DOCTYPE html>
+<html>
+<head><title>Render preview</title></head>
+<body style=\"background: #222; color: #ddd; font-family: monospace\">
+<img id=\"image\" src=\"/image.png\">
+<pre id=\"stats\"></pre>
+<script>
+setInterval(async () => {
+    document.getElementById('image').src = '/image.png?' + Date.now();
+    document.getElementById('stats').textContent = JSON.stringify(await (await fetch('/stats')).json(), null, 2);
+}, 2000);
+</script>
+</body>
+</html>
+";
+
+// What the server has to show, updated by the render.
+struct Snapshot {
+    png: Vec<u8>, // Encoded image of the last finished pass, empty before the first
+    pass: u32,
+    passes: u32,
+    pixels_done: usize,
+    pixels_total: usize,
+    samples_per_pixel: f64, // Mean over the image at the last finished pass
+    started: Instant,
+}
+
+pub struct PreviewServer {
+    addr: SocketAddr,
+    snapshot: Arc<Mutex<Snapshot>>,
+}
+
+impl PreviewServer {
+    // Starts serving on addr from a thread of its own, which lives as long as the program.
+    pub fn start(addr: impl ToSocketAddrs, passes: u32) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let snapshot = Arc::new(Mutex::new(Snapshot {
+            png: Vec::new(),
+            pass: 0,
+            passes,
+            pixels_done: 0,
+            pixels_total: 0,
+            samples_per_pixel: 0.0,
+            started: Instant::now(),
+        }));
+
+        let shared = Arc::clone(&snapshot);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A browser that goes quiet mid-request or stops reading the response only drops
+                // its own connection.
+                let _ = respond(stream, &shared);
+            }
+        });
+        Ok(PreviewServer { addr, snapshot })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn set_progress(&self, pixels_done: usize, pixels_total: usize) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        snapshot.pixels_done = pixels_done;
+        snapshot.pixels_total = pixels_total;
+    }
+
+    pub fn set_image(&self, image: &Accumulation, pass: u32) {
+        // Encoded before taking the lock, so requests aren't held up meanwhile.
+        let png = image.to_framebuffer().encode_png().unwrap_or_default();
+        let mut samples = 0;
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                samples += image.count(i, j);
+            }
+        }
+        let pixels = (image.width() as usize * image.height() as usize).max(1);
+
+        let mut snapshot = self.snapshot.lock().unwrap();
+        snapshot.png = png;
+        snapshot.pass = pass;
+        snapshot.samples_per_pixel = samples as f64 / pixels as f64;
+    }
+}
+
+fn stats_json(snapshot: &Snapshot) -> String {
+    format!(
+        "{{\"pass\": {}, \"passes\": {}, \"pixels_done\": {}, \"pixels_total\": {}, \"samples_per_pixel\": {}, \"elapsed_seconds\": {:.3}}}",
+        snapshot.pass,
+        snapshot.passes,
+        snapshot.pixels_done,
+        snapshot.pixels_total,
+        snapshot.samples_per_pixel,
+        snapshot.started.elapsed().as_secs_f64()
+    )
+}
+
+fn respond(stream: TcpStream, snapshot: &Mutex<Snapshot>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    // Only the path matters, without any query string added to dodge caching.
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or(path);
+
+    let (status, content_type, body) = match path {
+        "/" => ("200 OK", "text/html", PAGE.as_bytes().to_vec()),
+        "/stats" => ("200 OK", "application/json", stats_json(&snapshot.lock().unwrap()).into_bytes()),
+        "/image.png" => {
+            let png = snapshot.lock().unwrap().png.clone();
+            if png.is_empty() {
+                ("503 Service Unavailable", "text/plain", b"no pass finished yet\n".to_vec())
+            } else {
+                ("200 OK", "image/png", png)
+            }
+        }
+        _ => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+    };
+
+    let mut writer = &stream;
+    write!(writer, "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+    use std::io::Read;
+
+    fn get(addr: SocketAddr, path: &str) -> (String, Vec<u8>) {
+        // Status line and body of a GET request.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        (head.lines().next().unwrap().to_string(), response[split + 4..].to_vec())
+    }
+
+    #[test]
+    fn test_serves_image_and_stats() {
+        let server = PreviewServer::start("127.0.0.1:0", 4).unwrap();
+        let addr = server.local_addr();
+        assert!(get(addr, "/image.png").0.contains("503"));
+
+        let mut image = Accumulation::new(3, 2, false);
+        for j in 0..2 {
+            for i in 0..3 {
+                image.add_sample(i, j, Vec3::new(0.25, 0.5, 1.0));
+                image.add_sample(i, j, Vec3::new(0.25, 0.5, 1.0));
+            }
+        }
+        server.set_progress(12, 24);
+        server.set_image(&image, 2);
+
+        let (status, png) = get(addr, "/image.png?123");
+        assert!(status.contains("200"));
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let (status, stats) = get(addr, "/stats");
+        assert!(status.contains("200"));
+        let stats = String::from_utf8(stats).unwrap();
+        for field in ["\"pass\": 2", "\"passes\": 4", "\"pixels_done\": 12", "\"pixels_total\": 24", "\"samples_per_pixel\": 2"] {
+            assert!(stats.contains(field), "{} in {}", field, stats);
+        }
+
+        assert!(get(addr, "/").1.starts_with(b"<!DOCTYPE html>"));
+        assert!(get(addr, "/elsewhere").0.contains("404"));
+    }
+}
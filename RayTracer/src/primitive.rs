@@ -0,0 +1,104 @@
+use crate::hit::{HitRecord, Hittable, HittableClone};
+use crate::hit_list::HittableList;
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+use crate::aabb::AABB;
+use crate::interval::Interval;
+use crate::material::Materials;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use std::sync::Arc;
+
+// A shape at a leaf of the BVH. The built-in shapes are held by value and matched on, so testing a
+// ray against them is a direct call rather than one through a vtable; Custom keeps any other
+// Hittable behind its Arc.
+#[derive(Clone)]
+pub enum Primitive {
+    Sphere(Sphere),
+    Quad(Quad),
+    Custom(Arc<dyn Hittable + Send + Sync>),
+}
+
+impl Primitive {
+    pub fn new(object: &Arc<dyn Hittable + Send + Sync>) -> Self {
+        object.as_primitive().unwrap_or_else(|| Primitive::Custom(object.clone()))
+    }
+
+    fn inner(&self) -> &dyn Hittable {
+        match self {
+            Primitive::Sphere(sphere) => sphere,
+            Primitive::Quad(quad) => quad,
+            Primitive::Custom(object) => object.as_ref(),
+        }
+    }
+}
+
+impl Hittable for Primitive {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        match self {
+            Primitive::Sphere(sphere) => sphere.hit(r, ray_t, rec),
+            Primitive::Quad(quad) => quad.hit(r, ray_t, rec),
+            Primitive::Custom(object) => object.hit(r, ray_t, rec),
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.inner().bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.inner().pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.inner().random(origin)
+    }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        self.inner().collect_emitters(materials, lights)
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        self.inner().emitted_power(materials)
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        self.inner().sample_surface()
+    }
+
+    fn as_primitive(&self) -> Option<Primitive> {
+        Some(self.clone())
+    }
+}
+
+impl HittableClone for Primitive {
+    fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
+        match self {
+            Primitive::Custom(object) => object.clone(),
+            _ => Arc::new(self.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::Translate;
+
+    #[test]
+    fn test_built_in_shapes_are_held_by_value() {
+        let sphere: Arc<dyn Hittable + Send + Sync> = Arc::new(Sphere::new(Point3::zero(), 1.0, None));
+        let quad: Arc<dyn Hittable + Send + Sync> = Arc::new(Quad::new(Point3::zero(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), None));
+        let moved: Arc<dyn Hittable + Send + Sync> = Arc::new(Translate::new(sphere.clone(), Vec3::new(0.0, 3.0, 0.0)));
+        assert!(matches!(Primitive::new(&sphere), Primitive::Sphere(_)));
+        assert!(matches!(Primitive::new(&quad), Primitive::Quad(_)));
+        assert!(matches!(Primitive::new(&moved), Primitive::Custom(_)));
+
+        // Custom shapes are still hit through their own Hittable.
+        let r = Ray::new(Point3::new(0.0, 3.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::default();
+        assert!(Primitive::new(&moved).hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,416 @@
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::perlin::Perlin;
+use crate::texture::Texture;
+
+// Piecewise-linear map from [0, 1] to colours.
+#[derive(Clone)]
+pub struct ColorRamp {
+    stops: Vec<(f64, Color)>, // Sorted by position
+}
+
+impl ColorRamp {
+    pub fn new(stops: Vec<(f64, Color)>) -> Self {
+        let mut stops = stops;
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp { stops }
+    }
+
+    pub fn two(c0: Color, c1: Color) -> Self {
+        ColorRamp::new(vec![(0.0, c0), (1.0, c1)])
+    }
+
+    pub fn grey() -> Self {
+        ColorRamp::two(Color::zero(), Color::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn sample(&self, t: f64) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::new(t, t, t),
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+
+        let i = self.stops.partition_point(|stop| stop.0 <= t);
+        let (t0, c0) = self.stops[i - 1];
+        let (t1, c1) = self.stops[i];
+        let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 0.0 };
+        (1.0 - f) * c0 + f * c1
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FractalKind {
+    Fbm,
+    Turbulence, // Sum of absolute octaves, billowy
+    Ridged,
+}
+
+// Octave-summed Perlin noise returning values in [0, 1].
+#[derive(Clone)]
+pub struct Fractal {
+    noise: Perlin,
+    kind: FractalKind,
+    octaves: u32,
+    lacunarity: f64, // Frequency multiplier between octaves
+    gain: f64, // Amplitude multiplier between octaves
+}
+
+impl Fractal {
+    pub fn new(seed: u64) -> Self {
+        Fractal {
+            noise: Perlin::with_seed(seed),
+            kind: FractalKind::Fbm,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: FractalKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    pub fn with_lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn value(&self, p: &Point3) -> f64 {
+        let value = match self.kind {
+            FractalKind::Fbm => 0.5 + 0.5 * self.noise.fbm(p, self.octaves, self.lacunarity, self.gain),
+            FractalKind::Turbulence => self.noise.turbulence(p, self.octaves, self.lacunarity, self.gain),
+            FractalKind::Ridged => self.noise.ridged(p, self.octaves, self.lacunarity, self.gain),
+        };
+        value.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WorleyFeature {
+    F1, // Distance to the nearest feature point: round cells
+    F2MinusF1, // Gap between the two nearest: cell borders
+}
+
+// Cellular noise with one jittered feature point per unit cell.
+#[derive(Clone)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Worley { seed }
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64, n: u64) -> f64 {
+        // SplitMix64 finaliser over the cell coordinates, mapped to [0, 1).
+        let mut x = self.seed
+            ^ (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (j as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (k as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+            ^ n.wrapping_mul(0x27D4_EB2F_1656_67C5);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn feature_point(&self, i: i64, j: i64, k: i64) -> Point3 {
+        Point3::new(
+            i as f64 + self.hash(i, j, k, 0),
+            j as f64 + self.hash(i, j, k, 1),
+            k as f64 + self.hash(i, j, k, 2),
+        )
+    }
+
+    pub fn distances(&self, p: &Point3) -> (f64, f64) {
+        // Distances to the nearest and second nearest feature points.
+        let ci = p.x.floor() as i64;
+        let cj = p.y.floor() as i64;
+        let ck = p.z.floor() as i64;
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+
+        for i in ci - 1..=ci + 1 {
+            for j in cj - 1..=cj + 1 {
+                for k in ck - 1..=ck + 1 {
+                    let d = (self.feature_point(i, j, k) - *p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+
+        (f1, f2)
+    }
+}
+
+#[derive(Clone)]
+pub struct FractalTexture {
+    fractal: Fractal,
+    scale: f64,
+    ramp: ColorRamp,
+}
+
+impl FractalTexture {
+    pub fn new(fractal: Fractal) -> Self {
+        FractalTexture { fractal, scale: 1.0, ramp: ColorRamp::grey() }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for FractalTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        self.ramp.sample(self.fractal.value(&(self.scale * *p)))
+    }
+}
+
+#[derive(Clone)]
+pub struct WorleyTexture {
+    worley: Worley,
+    feature: WorleyFeature,
+    scale: f64,
+    ramp: ColorRamp,
+}
+
+impl WorleyTexture {
+    pub fn new(seed: u64) -> Self {
+        WorleyTexture {
+            worley: Worley::new(seed),
+            feature: WorleyFeature::F1,
+            scale: 1.0,
+            ramp: ColorRamp::grey(),
+        }
+    }
+
+    pub fn with_feature(mut self, feature: WorleyFeature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let (f1, f2) = self.worley.distances(&(self.scale * *p));
+        let t = match self.feature {
+            WorleyFeature::F1 => f1,
+            WorleyFeature::F2MinusF1 => f2 - f1,
+        };
+        self.ramp.sample(t.clamp(0.0, 1.0))
+    }
+}
+
+// Concentric rings around the y axis, wobbled by noise.
+#[derive(Clone)]
+pub struct WoodTexture {
+    fractal: Fractal,
+    scale: f64,
+    rings: f64, // Rings per unit of radius
+    distortion: f64, // How far the noise pushes the rings, in rings
+    ramp: ColorRamp,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64) -> Self {
+        WoodTexture {
+            fractal: Fractal::new(seed).with_octaves(4),
+            scale: 1.0,
+            rings: 8.0,
+            distortion: 1.5,
+            ramp: ColorRamp::two(Color::new(0.8, 0.55, 0.3), Color::new(0.45, 0.25, 0.1)),
+        }
+    }
+
+    pub fn with_fractal(mut self, fractal: Fractal) -> Self {
+        self.fractal = fractal;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_rings(mut self, rings: f64, distortion: f64) -> Self {
+        self.rings = rings;
+        self.distortion = distortion;
+        self
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let q = self.scale * *p;
+        let radius = (q.x * q.x + q.z * q.z).sqrt();
+        let ring = self.rings * radius + self.distortion * self.fractal.value(&q);
+        // Sharpen the rings so the dark late wood is thinner than the light early wood.
+        let t = (ring - ring.floor()).powf(3.0);
+        self.ramp.sample(t)
+    }
+}
+
+// Veins from a sine wave along an axis, bent by turbulence.
+#[derive(Clone)]
+pub struct MarbleTexture {
+    fractal: Fractal,
+    scale: f64,
+    axis: Vec3, // Direction the veins are stacked along
+    distortion: f64,
+    ramp: ColorRamp,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64) -> Self {
+        MarbleTexture {
+            fractal: Fractal::new(seed).with_kind(FractalKind::Turbulence).with_octaves(7),
+            scale: 1.0,
+            axis: Vec3::new(0.0, 0.0, 1.0),
+            distortion: 10.0,
+            ramp: ColorRamp::grey(),
+        }
+    }
+
+    pub fn with_fractal(mut self, fractal: Fractal) -> Self {
+        self.fractal = fractal;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_veins(mut self, axis: Vec3, distortion: f64) -> Self {
+        self.axis = axis.unit();
+        self.distortion = distortion;
+        self
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let phase = self.scale * p.dot(self.axis) + self.distortion * self.fractal.value(p);
+        self.ramp.sample(0.5 * (1.0 + phase.sin()))
+    }
+}
+
+// Linear blend through a ramp from one point to another.
+#[derive(Clone)]
+pub struct GradientTexture {
+    from: Point3,
+    to: Point3,
+    ramp: ColorRamp,
+}
+
+impl GradientTexture {
+    pub fn new(from: Point3, to: Point3, ramp: ColorRamp) -> Self {
+        GradientTexture { from, to, ramp }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let axis = self.to - self.from;
+        let length_squared = axis.dot(axis);
+        if length_squared <= 0.0 {
+            return self.ramp.sample(0.0);
+        }
+        self.ramp.sample((*p - self.from).dot(axis) / length_squared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_noise_is_repeatable() {
+        let p = Point3::new(1.3, -0.7, 2.9);
+        let a = Fractal::new(7).value(&p);
+        assert_eq!(a, Fractal::new(7).value(&p));
+        assert_ne!(a, Fractal::new(8).value(&p));
+    }
+
+    #[test]
+    fn test_fractal_range() {
+        for kind in [FractalKind::Fbm, FractalKind::Turbulence, FractalKind::Ridged] {
+            let fractal = Fractal::new(1).with_kind(kind).with_octaves(5).with_lacunarity(2.1).with_gain(0.6);
+            for i in 0..200 {
+                let p = Point3::new(i as f64 * 0.37, i as f64 * 0.11, -(i as f64) * 0.23);
+                let value = fractal.value(&p);
+                assert!((0.0..=1.0).contains(&value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_worley_distances() {
+        let worley = Worley::new(3);
+        let feature = worley.feature_point(2, -1, 0);
+        let (f1, f2) = worley.distances(&feature);
+        assert!(f1 < 1e-12);
+        assert!(f2 > 0.0);
+
+        let (f1, f2) = worley.distances(&Point3::new(0.4, 5.5, -3.2));
+        assert!(f1 <= f2);
+    }
+
+    #[test]
+    fn test_ramp_interpolates() {
+        let ramp = ColorRamp::new(vec![(1.0, Color::new(0.0, 0.0, 1.0)), (0.0, Color::new(1.0, 0.0, 0.0))]);
+        assert!((ramp.sample(0.25) - Color::new(0.75, 0.0, 0.25)).length() < 1e-12);
+        assert!((ramp.sample(-1.0) - Color::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((ramp.sample(2.0) - Color::new(0.0, 0.0, 1.0)).length() < 1e-12);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::hit::{HitRecord, Hittable, HittableClone};
 use crate::vec3::Vec3;
 type Point3 = Vec3;
-use crate::material::Material;
+use crate::material::{MaterialId, Materials};
 use crate::aabb::AABB;
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::hit_list::HittableList;
+use crate::primitive::Primitive;
 use std::sync::Arc;
 use crate::rtweekend::random_double;
+use crate::stats::{count, Counter};
 
 #[derive(Clone)]
 pub struct Quad {
@@ -15,7 +17,7 @@ pub struct Quad {
     u: Vec3,
     v: Vec3,
     w: Vec3,
-    mat: Option<Arc<dyn Material + Send + Sync>>,
+    mat: Option<MaterialId>,
     bbox: AABB,
     normal: Vec3,
     d: f64,
@@ -23,7 +25,7 @@ pub struct Quad {
 }
 
 impl Quad {
-    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Option<Arc<dyn Material + Send + Sync>>) -> Self {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Option<MaterialId>) -> Self {
         let n = u.cross(v);
         let normal = n.unit();
         let d = normal.dot(q);
@@ -69,6 +71,7 @@ impl Hittable for Quad {
     }
     
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        count(Counter::PrimitiveTest);
         let denom = self.normal.dot(*r.direction());
 
         // No hit if the ray is parallel to the plane.
@@ -93,8 +96,11 @@ impl Hittable for Quad {
 
         rec.t = t;
         rec.p = intersection;
-        rec.mat = self.mat.clone();
+        rec.mat = self.mat;
         rec.set_face_normal(r, &self.normal);
+        rec.dpdu = self.u;
+        rec.dpdv = self.v;
+        rec.shading = None;
 
         true
     }
@@ -114,6 +120,30 @@ impl Hittable for Quad {
         let p = self.q + (random_double(0.0, 1.0) * self.u) + (random_double(0.0, 1.0) * self.v);
         p - *origin
     }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        if self.mat.is_some_and(|mat| materials.get(mat).is_emissive()) {
+            lights.add(self.clone_box());
+        }
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        // Diffuse emission from the front face: pi * radiance per unit area.
+        let radiance = match self.mat {
+            Some(mat) => materials.get(mat).emission_estimate(),
+            None => return 0.0,
+        };
+        std::f64::consts::PI * self.area * (radiance.x + radiance.y + radiance.z) / 3.0
+    }
+
+    fn sample_surface(&self) -> Option<(Point3, Vec3)> {
+        let p = self.q + (random_double(0.0, 1.0) * self.u) + (random_double(0.0, 1.0) * self.v);
+        Some((p, self.normal))
+    }
+
+    fn as_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::Quad(self.clone()))
+    }
 }
 
 impl HittableClone for Quad {
@@ -122,7 +152,7 @@ impl HittableClone for Quad {
     }
 }
 
-pub fn make_box(a: Point3, b: Point3, mat: Option<Arc<dyn Material + Send + Sync>>) -> HittableList {
+pub fn make_box(a: Point3, b: Point3, mat: Option<MaterialId>) -> HittableList {
     // Returns the 3D box (six sides) that contains the two opposite vertices a & b.
 
     let mut sides = HittableList::new();
@@ -147,38 +177,135 @@ pub fn make_box(a: Point3, b: Point3, mat: Option<Arc<dyn Material + Send + Sync
         Point3::new(min.x(), min.y(), max.z()),
         dx,
         dy,
-        mat.clone(),
+        mat,
     ))); // front
     sides.add(Arc::new(Quad::new(
         Point3::new(max.x(), min.y(), max.z()),
         -1.0 * dz,
         dy,
-        mat.clone(),
+        mat,
     ))); // right
     sides.add(Arc::new(Quad::new(
         Point3::new(max.x(), min.y(), min.z()),
         -1.0 * dx,
         dy,
-        mat.clone(),
+        mat,
     ))); // back
     sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), min.y(), min.z()),
         dz,
         dy,
-        mat.clone(),
+        mat,
     ))); // left
     sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), max.y(), max.z()),
         dx,
         -1.0 * dz,
-        mat.clone(),
+        mat,
     ))); // top
     sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), min.y(), min.z()),
         dx,
         dz,
-        mat.clone(),
+        mat,
     ))); // bottom
 
     sides
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtweekend::{random_int, seed_random};
+
+    fn random_quad() -> Quad {
+        // Not too thin, so the plane coordinates stay well conditioned.
+        loop {
+            let u = Vec3::random(-3.0, 3.0);
+            let v = Vec3::random(-3.0, 3.0);
+            if u.cross(v).length() > 0.5 {
+                return Quad::new(Point3::random(-5.0, 5.0), u, v, None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_rays_hit_inside() {
+        seed_random(454);
+        for _ in 0..1000 {
+            let quad = random_quad();
+            let (a, b) = (random_double(0.01, 0.99), random_double(0.01, 0.99));
+            let target = quad.q + a * quad.u + b * quad.v;
+            let origin = Point3::random(-20.0, 20.0);
+            let r = Ray::new(origin, target - origin, 0.0);
+            if quad.normal.dot(*r.direction()).abs() < 1e-3 {
+                continue;
+            }
+
+            let mut rec = HitRecord::default();
+            assert!(quad.hit(&r, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+            assert!((rec.t - 1.0).abs() < 1e-6);
+            assert!((rec.p - target).length() < 1e-6);
+            assert!((rec.u - a).abs() < 1e-6 && (rec.v - b).abs() < 1e-6);
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+            // The normal is the plane's, turned to face the ray.
+            assert_eq!(rec.front_face, quad.normal.dot(*r.direction()) < 0.0);
+            assert!(rec.normal.dot(*r.direction()) < 0.0);
+            assert!(rec.normal.cross(quad.normal).length() < 1e-9);
+            assert!(quad.bounding_box().contains(rec.p, 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_rays_outside_the_edges_miss() {
+        seed_random(455);
+        for _ in 0..1000 {
+            let quad = random_quad();
+            // Somewhere in the plane, but past one of the edges.
+            let (a, b) = match random_int(0, 3) {
+                0 => (random_double(-2.0, -0.01), random_double(0.0, 1.0)),
+                1 => (random_double(1.01, 3.0), random_double(0.0, 1.0)),
+                2 => (random_double(0.0, 1.0), random_double(-2.0, -0.01)),
+                _ => (random_double(0.0, 1.0), random_double(1.01, 3.0)),
+            };
+            let target = quad.q + a * quad.u + b * quad.v;
+            let origin = target + quad.normal * random_double(1.0, 10.0);
+            let r = Ray::new(origin, target - origin, 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(!quad.hit(&r, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+        }
+    }
+
+    #[test]
+    fn test_parallel_and_out_of_range_rays_miss() {
+        let quad = Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), None);
+        let mut rec = HitRecord::default();
+
+        let parallel = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(!quad.hit(&parallel, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+
+        let head_on = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!quad.hit(&head_on, Interval::with_values(0.0, 0.5), &mut rec));
+        assert!(quad.hit(&head_on, Interval::with_values(0.0, 2.0), &mut rec));
+        assert!(rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.5, 0.5));
+    }
+
+    #[test]
+    fn test_box_faces_point_outwards() {
+        // Every ray from outside a box meets it first on a front face.
+        seed_random(456);
+        let sides = make_box(Point3::new(-1.0, -2.0, -3.0), Point3::new(1.0, 2.0, 3.0), None);
+        for _ in 0..1000 {
+            let origin = 10.0 * Vec3::random_unit_vector();
+            let target = Point3::new(random_double(-1.0, 1.0), random_double(-2.0, 2.0), random_double(-3.0, 3.0));
+            let r = Ray::new(origin, target - origin, 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(sides.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+            assert!(rec.front_face);
+            assert!(sides.bounding_box().contains(rec.p, 1e-9));
+        }
+    }
+}
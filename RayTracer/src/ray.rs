@@ -1,20 +1,22 @@
-use std::f64::INFINITY;
-
 use crate::hit_list::HittableList;
 use crate::interval::Interval;
-use crate::material::ScatterRecord;
+use crate::material::{Materials, ScatterRecord};
 use crate::vec3::Vec3;
 type Point3 = Vec3;
 use crate::hit::{HitRecord, Hittable};
-use crate::pdf::{Pdf, CosinePdf, HittablePdf, MixturePdf};
+use crate::light::Light;
+use crate::pdf::{Pdf, LightPdf, MixturePdf};
+use crate::light_sampler::LightSampler;
+use crate::photon_map::PhotonMap;
+use crate::stats::{count, Counter};
 use std::sync::Arc;
-use crate::rtweekend::random_double;
 
 #[derive(Default, Clone, Copy)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
     tm: f64,
+    spread: f64, // Angle by which the ray's pixel cone widens per unit of distance, zero for a thin ray
 }
 
 impl Ray {
@@ -23,9 +25,15 @@ impl Ray {
             origin,
             direction,
             tm,
+            spread: 0.0,
         }
     }
 
+    pub fn with_spread(mut self, spread: f64) -> Self {
+        self.spread = spread;
+        self
+    }
+
     pub fn origin(&self) -> &Point3 {
         &self.origin
     }
@@ -39,61 +47,121 @@ impl Ray {
     }
 
     pub fn at(&self, t: f64) -> Point3 {
-        self.origin.clone() + self.direction.clone() * t
+        self.origin + self.direction * t
+    }
+
+    pub fn footprint_width(&self, t: f64) -> f64 {
+        // Width of the pixel cone at parameter t.
+        self.spread * t * self.direction.length()
     }
 }
 
+// Where a path stands with respect to a caustic photon map, if one is in use.
+#[derive(Clone, Copy)]
+struct Caustics<'a> {
+    map: Option<&'a PhotonMap>,
+    after_diffuse: bool, // The path has bounced off a diffuse surface
+    through_specular: bool, // and only off mirrors and glass since
+}
+
 impl Ray {
-    pub fn ray_color(&self, background: Vec3, depth: u32, world: &HittableList, lights: &HittableList) -> Vec3 {
+    pub fn ray_color(&self, background: Vec3, depth: u32, world: &HittableList, materials: &Materials, lights: &LightSampler, delta_lights: &[Arc<dyn Light + Send + Sync>]) -> Vec3 {
+        let caustics = Caustics { map: None, after_diffuse: false, through_specular: false };
+        self.trace(background, depth, world, materials, lights, delta_lights, caustics)
+    }
+
+    // Path tracing with caustics taken from the photon map at every diffuse bounce. Light that the
+    // path would find through mirrors or glass after a diffuse bounce is in the map already, so it
+    // is not counted again.
+    #[allow(clippy::too_many_arguments)]
+    pub fn ray_color_with_caustics(&self, background: Vec3, depth: u32, world: &HittableList, materials: &Materials, lights: &LightSampler, delta_lights: &[Arc<dyn Light + Send + Sync>], caustics: &PhotonMap) -> Vec3 {
+        let caustics = Caustics { map: Some(caustics), after_diffuse: false, through_specular: false };
+        self.trace(background, depth, world, materials, lights, delta_lights, caustics)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn trace(&self, background: Vec3, depth: u32, world: &HittableList, materials: &Materials, lights: &LightSampler, delta_lights: &[Arc<dyn Light + Send + Sync>], caustics: Caustics) -> Vec3 {
         // If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth <= 0 {
+        if depth == 0 {
             return Vec3::new(0.0, 0.0, 0.0);
         }
 
         let mut rec = HitRecord::default();
 
         // If the ray hits nothing, return the background color.
-        if !world.hit(self, Interval::with_values(0.001, INFINITY), &mut rec) {
+        if !world.hit(self, Interval::with_values(0.001, f64::INFINITY), &mut rec) {
             return background;
         }
+        rec.set_footprint(self);
+        rec.apply_shading_normal();
 
-        let mat = rec.mat.as_ref().unwrap();
+        let mat = materials.get(rec.mat.unwrap());
         let mut srec = ScatterRecord::default();
-        let color_from_emission = mat.emitted(self, &rec, rec.u, rec.v, &rec.p);
+        let color_from_emission = if caustics.after_diffuse && caustics.through_specular {
+            Vec3::zero()
+        } else {
+            mat.emitted(self, &rec, rec.u, rec.v, &rec.p)
+        };
 
         if !mat.scatter(self, &rec, &mut srec) {
-            // println!("{} {} {}", color_from_emission.x, color_from_emission.y, color_from_emission.z);
             return color_from_emission;
         }
 
         if srec.skip_pdf {
-            return srec.attenuation * srec.skip_pdf_ray.ray_color(background, depth - 1, world, lights);
+            let caustics = Caustics { through_specular: true, ..caustics };
+            count(Counter::SecondaryRay);
+            return srec.attenuation * srec.skip_pdf_ray.trace(background, depth - 1, world, materials, lights, delta_lights, caustics);
         }
 
-        let light_ptr = Arc::new(HittablePdf::new(lights.clone(), rec.p));
-        let p = MixturePdf::new(light_ptr, srec.pdf_ptr);
+        // Delta lights can't be found by scattering, so they are always sampled directly.
+        let color_from_delta_lights = self.delta_light_color(&rec, &srec, world, materials, delta_lights);
+        let color_from_caustics = caustics.map.map_or(Vec3::zero(), |map| map.caustic_radiance(self, &rec, &srec, materials));
+
+        // Without sampled lights, fall back to the material's own distribution. Both live on the
+        // stack, so a bounce allocates nothing.
+        let light_pdf = LightPdf::new(lights, rec.p);
+        let mixture_pdf = MixturePdf::new(&light_pdf, &srec.pdf);
+        let p: &dyn Pdf = if lights.is_empty() { &srec.pdf } else { &mixture_pdf };
 
         let scattered = Ray::new(rec.p, p.generate(), self.time());
         let pdf_val = p.value(scattered.direction());
 
         let scattering_pdf = mat.scattering_pdf(self, &rec, &scattered);
 
-        let sample_color = scattered.ray_color(background, depth - 1, world, lights);
+        let caustics = Caustics { after_diffuse: caustics.map.is_some(), through_specular: false, ..caustics };
+        count(Counter::SecondaryRay);
+        let sample_color = scattered.trace(background, depth - 1, world, materials, lights, delta_lights, caustics);
         let color_from_scatter = (srec.attenuation * scattering_pdf * sample_color) / pdf_val;
 
-        
-        // let p0 = Arc::new(HittablePdf::new(lights.clone(), rec.p));
-        // let p1 = Arc::new(CosinePdf::new(rec.normal));
-        // let mixed_pdf = MixturePdf::new(p0, p1);
-
-        // let scattered = Ray::new(rec.p, mixed_pdf.generate(), self.time());
-        // let pdf_val = mixed_pdf.value(scattered.direction());
-
-        // let scattering_pdf = mat.scattering_pdf(self, &rec, &scattered);
+        color_from_emission + color_from_delta_lights + color_from_caustics + color_from_scatter
+    }
 
-        // let sample_color = scattered.ray_color(background, depth - 1, world, lights);
-        // let color_from_scatter = (srec.attenuation * scattering_pdf * sample_color) / pdf_val;
+    pub fn delta_light_color(&self, rec: &HitRecord, srec: &ScatterRecord, world: &HittableList, materials: &Materials, delta_lights: &[Arc<dyn Light + Send + Sync>]) -> Vec3 {
+        // Light reaching the hit from each delta light, using a shadow ray to test visibility.
+        let mat = materials.get(rec.mat.unwrap());
+        let mut color = Vec3::zero();
+
+        for light in delta_lights {
+            let sample = match light.sample(&rec.p) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            // Directions the material never scatters into, such as below a diffuse surface, get no light.
+            if srec.pdf.value(&sample.direction) <= 0.0 {
+                continue;
+            }
+
+            let shadow_ray = Ray::new(rec.p, sample.direction, self.time());
+            let mut shadow_rec = HitRecord::default();
+            count(Counter::ShadowRay);
+            if world.hit(&shadow_ray, Interval::with_values(0.001, sample.distance - 0.001), &mut shadow_rec) {
+                continue;
+            }
+
+            let scattering_pdf = mat.scattering_pdf(self, rec, &shadow_ray);
+            color += srec.attenuation * scattering_pdf * sample.radiance;
+        }
 
-        color_from_emission + color_from_scatter
+        color
     }
 }
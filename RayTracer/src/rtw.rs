@@ -1,85 +1,408 @@
-use opencv::core::{MatTraitConst, VecN};
-use opencv::imgcodecs::{imread, IMREAD_COLOR};
 use crate::vec3::Vec3;
+type Color = Vec3;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WrapMode {
+    Repeat, // Tile the image
+    Mirror, // Tile the image, flipping every other copy
+    Clamp,  // Stretch the edge texels outwards
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+    Trilinear,        // Bilinear lookups blended between the two nearest mipmap levels
+    Anisotropic(u32), // Up to this many trilinear lookups along the long axis of the footprint
+}
+
+// RGBA sample; colour channels are unbounded for HDR images, alpha is in [0,1].
+pub type Texel = [f64; 4];
+
+fn lerp(a: Texel, b: Texel, t: f64) -> Texel {
+    [
+        a[0] + t * (b[0] - a[0]),
+        a[1] + t * (b[1] - a[1]),
+        a[2] + t * (b[2] - a[2]),
+        a[3] + t * (b[3] - a[3]),
+    ]
+}
+
+pub fn texel_color(texel: Texel) -> Color {
+    Color::new(texel[0], texel[1], texel[2])
+}
+
+#[derive(Debug)]
+pub struct ImageLoadError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ImageLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not load image {}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ImageLoadError {}
 
 #[derive(Clone)]
 pub struct RtwImage {
-    pub img_data: opencv::core::Mat,
+    pixels: Vec<[f32; 4]>, // RGBA row by row from the top; 8 and 16 bit images are scaled to [0,1]
     pub width: usize,
     pub height: usize,
 }
 
 impl RtwImage {
-    pub fn new(name: &str) -> Self {
-        let path = format!("images/{}", name);
-        println!("Loading texture: {}", path);
-        let img_data = imread(&path, IMREAD_COLOR).expect("Image reading error!");
-        let width = img_data.cols() as usize;
-        let height = img_data.rows() as usize;
-        RtwImage {
-            img_data,
-            width,
-            height,
+    pub fn empty() -> Self {
+        RtwImage { pixels: Vec::new(), width: 0, height: 0 }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ImageLoadError> {
+        let path = RtwImage::find(path.as_ref());
+
+        let is_hdr = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("hdr"));
+        let decoded = if is_hdr { RtwImage::open_hdr(&path) } else { image::open(&path) };
+
+        match decoded {
+            Ok(img) => {
+                let img = img.into_rgba32f();
+                let (width, height) = (img.width() as usize, img.height() as usize);
+                let pixels = img.pixels().map(|p| p.0).collect();
+                Ok(RtwImage::from_pixels(width, height, pixels))
+            }
+            #[cfg(feature = "opencv")]
+            Err(_) => RtwImage::load_opencv(&path),
+            #[cfg(not(feature = "opencv"))]
+            Err(err) => Err(ImageLoadError { path, message: err.to_string() }),
+        }
+    }
+
+    fn open_hdr(path: &Path) -> image::ImageResult<image::DynamicImage> {
+        // image::open converts Radiance HDR files to 8 bit, so decode them to floats directly.
+        let reader = std::io::BufReader::new(std::fs::File::open(path).map_err(image::ImageError::IoError)?);
+        let decoder = image::codecs::hdr::HdrDecoder::new(reader)?;
+        let meta = decoder.metadata();
+        let pixels: Vec<f32> = decoder.read_image_hdr()?.into_iter().flat_map(|p| p.0).collect();
+
+        image::Rgb32FImage::from_raw(meta.width, meta.height, pixels)
+            .map(image::DynamicImage::ImageRgb32F)
+            .ok_or_else(|| image::ImageError::Parameter(image::error::ParameterError::from_kind(image::error::ParameterErrorKind::DimensionMismatch)))
+    }
+
+    fn find(path: &Path) -> PathBuf {
+        // Use the path as given if it exists, otherwise look for it in $RTW_IMAGES and images/.
+        if path.exists() || path.is_absolute() {
+            return path.to_path_buf();
         }
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        if let Ok(dir) = std::env::var("RTW_IMAGES") {
+            dirs.push(PathBuf::from(dir));
+        }
+        dirs.push(PathBuf::from("images"));
+        dirs.push(PathBuf::from("../images"));
+
+        dirs.into_iter()
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.exists())
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    #[cfg(feature = "opencv")]
+    fn load_opencv(path: &Path) -> Result<Self, ImageLoadError> {
+        use opencv::core::{MatTraitConst, VecN, CV_16U, CV_32F, CV_8U};
+        use opencv::imgcodecs::{imread, IMREAD_UNCHANGED};
+
+        let error = |message: String| ImageLoadError { path: path.to_path_buf(), message };
+
+        let img_data = imread(&path.to_string_lossy(), IMREAD_UNCHANGED).map_err(|e| error(e.to_string()))?;
+        if img_data.empty() {
+            return Err(error("unsupported or missing file".to_string()));
+        }
+
+        let scale = match img_data.depth() {
+            CV_8U => 1.0 / 255.0,
+            CV_16U => 1.0 / 65535.0,
+            CV_32F => 1.0,
+            depth => return Err(error(format!("unsupported pixel depth {}", depth))),
+        };
+        let mut data = opencv::core::Mat::default();
+        img_data.convert_to(&mut data, CV_32F, scale, 0.0).map_err(|e| error(e.to_string()))?;
+
+        let width = data.cols() as usize;
+        let height = data.rows() as usize;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                // OpenCV stores pixels as BGR(A).
+                let pixel = match data.channels() {
+                    1 => {
+                        let g = *data.at_2d::<f32>(y, x).map_err(|e| error(e.to_string()))?;
+                        [g, g, g, 1.0]
+                    }
+                    3 => {
+                        let c = data.at_2d::<VecN<f32, 3>>(y, x).map_err(|e| error(e.to_string()))?;
+                        [c[2], c[1], c[0], 1.0]
+                    }
+                    4 => {
+                        let c = data.at_2d::<VecN<f32, 4>>(y, x).map_err(|e| error(e.to_string()))?;
+                        [c[2], c[1], c[0], c[3]]
+                    }
+                    n => return Err(error(format!("unsupported channel count {}", n))),
+                };
+                pixels.push(pixel);
+            }
+        }
+
+        Ok(RtwImage::from_pixels(width, height, pixels))
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<[f32; 4]>) -> Self {
+        assert_eq!(pixels.len(), width * height, "Pixel buffer does not match image size");
+        RtwImage { pixels, width, height }
     }
 
-    pub fn get_color(&self, mut u: f64, mut v: f64) -> Vec3 {
-        if u < 0.0 { u = 0.0; }
-        if u > 1.0 { u = 1.0; }
-        if v < 0.0 { v = 0.0; }
-        if v > 1.0 { v = 1.0; }
-
-        let u_img = u * self.width as f64;
-        let v_img = (1.0 - v) * self.height as f64;
-        let color: &VecN<u8, 3> = self.img_data.at_2d(v_img as i32, u_img as i32).unwrap();
-
-        // println!("Color: {:?}", color);
-        Vec3::new(color[2] as f64, color[1] as f64, color[0] as f64)
-    }
-
-    pub fn get_color_bilinear(&self, mut u: f64, mut v: f64) -> Vec3 {
-        // 在此实现双线性插值函数, 并替换掉get_color
-        // Ensure u and v are within the [0, 1] range
-        u = u.clamp(0.0, 1.0);
-        v = v.clamp(0.0, 1.0);
-
-        // Calculate the surrounding pixel indices
-        let u_img = u * (self.width as f64 - 1.0);
-        let v_img = (1.0 - v) * (self.height as f64 - 1.0);
-        let x = u_img.floor() as i32;
-        let y = v_img.floor() as i32;
-        let x1 = (u_img.ceil() as i32).min(self.width as i32 - 1);
-        let y1 = (v_img.ceil() as i32).min(self.height as i32 - 1);
-
-        // Calculate the fractional parts of u and v
-        let u_ratio = u_img - x as f64;
-        let v_ratio = v_img - y as f64;
-        let u_opposite = 1.0 - u_ratio;
-        let v_opposite = 1.0 - v_ratio;
-
-        // Retrieve the colors of the four surrounding pixels
-        let top_left: &VecN<u8, 3> = self.img_data.at_2d(y, x).unwrap();
-        let top_right: &VecN<u8, 3> = self.img_data.at_2d(y, x1).unwrap();
-        let bottom_left: &VecN<u8, 3> = self.img_data.at_2d(y1, x).unwrap();
-        let bottom_right: &VecN<u8, 3> = self.img_data.at_2d(y1, x1).unwrap();
-
-        // Perform the bilinear interpolation
-        let color_top = Vec3::new(
-            top_left[2] as f64 * u_opposite + top_right[2] as f64 * u_ratio,
-            top_left[1] as f64 * u_opposite + top_right[1] as f64 * u_ratio,
-            top_left[0] as f64 * u_opposite + top_right[0] as f64 * u_ratio,
-        );
-        let color_bottom = Vec3::new(
-            bottom_left[2] as f64 * u_opposite + bottom_right[2] as f64 * u_ratio,
-            bottom_left[1] as f64 * u_opposite + bottom_right[1] as f64 * u_ratio,
-            bottom_left[0] as f64 * u_opposite + bottom_right[0] as f64 * u_ratio,
-        );
-
-        // println!("Color: {:?}", color_top);
-        Vec3::new(
-            color_top.x * v_opposite + color_bottom.x * v_ratio,
-            color_top.y * v_opposite + color_bottom.y * v_ratio,
-            color_top.z * v_opposite + color_bottom.z * v_ratio,
-        )
-    }
-}
\ No newline at end of file
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    pub fn texel(&self, x: usize, y: usize) -> Texel {
+        let [r, g, b, a] = self.pixels[y * self.width + x];
+        [r as f64, g as f64, b as f64, a as f64]
+    }
+
+    pub fn nearest(&self, u: f64, v: f64, wrap: WrapMode) -> Texel {
+        let x = (u * self.width as f64).floor() as i64;
+        let y = ((1.0 - v) * self.height as f64).floor() as i64;
+        self.texel(wrap_index(x, self.width, wrap), wrap_index(y, self.height, wrap))
+    }
+
+    pub fn bilinear(&self, u: f64, v: f64, wrap: WrapMode) -> Texel {
+        // Texel centers sit at half-integer image coordinates.
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let x0i = wrap_index(x0 as i64, self.width, wrap);
+        let x1i = wrap_index(x0 as i64 + 1, self.width, wrap);
+        let y0i = wrap_index(y0 as i64, self.height, wrap);
+        let y1i = wrap_index(y0 as i64 + 1, self.height, wrap);
+
+        let top = lerp(self.texel(x0i, y0i), self.texel(x1i, y0i), fx);
+        let bottom = lerp(self.texel(x0i, y1i), self.texel(x1i, y1i), fx);
+        lerp(top, bottom, fy)
+    }
+
+    fn downsample(&self) -> RtwImage {
+        // Halve the image with a 2x2 box filter; odd edges reuse their last texel.
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0f32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (2 * x + dx).min(self.width - 1);
+                    let sy = (2 * y + dy).min(self.height - 1);
+                    let p = self.pixels[sy * self.width + sx];
+                    for c in 0..4 {
+                        sum[c] += p[c] * 0.25;
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+
+        RtwImage::from_pixels(width, height, pixels)
+    }
+}
+
+fn wrap_index(i: i64, n: usize, wrap: WrapMode) -> usize {
+    let n = n as i64;
+    let wrapped = match wrap {
+        WrapMode::Repeat => i.rem_euclid(n),
+        WrapMode::Mirror => {
+            let m = i.rem_euclid(2 * n);
+            if m < n { m } else { 2 * n - 1 - m }
+        }
+        WrapMode::Clamp => i.clamp(0, n - 1),
+    };
+    wrapped as usize
+}
+
+#[derive(Clone)]
+pub struct MipMap {
+    levels: Vec<RtwImage>, // levels[0] is the full image, each next one half the size
+}
+
+impl MipMap {
+    pub fn new(image: RtwImage) -> Self {
+        MipMap { levels: vec![image] }
+    }
+
+    pub fn base(&self) -> &RtwImage {
+        &self.levels[0]
+    }
+
+    pub fn build_levels(&mut self) {
+        if self.levels.len() > 1 || self.base().is_empty() {
+            return;
+        }
+        loop {
+            let last = self.levels.last().unwrap();
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            self.levels.push(next);
+        }
+    }
+
+    pub fn sample(&self, u: f64, v: f64, major: (f64, f64), minor: (f64, f64), filter: TextureFilter, wrap: WrapMode) -> Texel {
+        // major and minor are the two axes of the pixel footprint in uv space.
+        match filter {
+            TextureFilter::Nearest => self.base().nearest(u, v, wrap),
+            TextureFilter::Bilinear => self.base().bilinear(u, v, wrap),
+            TextureFilter::Trilinear => {
+                let width = self.texel_length(major).max(self.texel_length(minor));
+                self.trilinear(u, v, width, wrap)
+            }
+            TextureFilter::Anisotropic(max_samples) => {
+                let max_samples = max_samples.max(1);
+                let (major, minor) = if self.texel_length(major) >= self.texel_length(minor) { (major, minor) } else { (minor, major) };
+                let major_len = self.texel_length(major);
+
+                // Past the sample budget, blur more instead of taking more samples.
+                let minor_len = self.texel_length(minor).max(major_len / max_samples as f64);
+                let count = if minor_len > 0.0 {
+                    ((major_len / minor_len).ceil() as u32).clamp(1, max_samples)
+                } else {
+                    1
+                };
+
+                let mut sum = [0.0; 4];
+                for i in 0..count {
+                    let offset = (i as f64 + 0.5) / count as f64 - 0.5;
+                    let texel = self.trilinear(u + offset * major.0, v + offset * major.1, minor_len, wrap);
+                    for c in 0..4 {
+                        sum[c] += texel[c] / count as f64;
+                    }
+                }
+                sum
+            }
+        }
+    }
+
+    fn texel_length(&self, axis: (f64, f64)) -> f64 {
+        // Length of a uv-space vector measured in texels of the full image.
+        let du = axis.0 * self.base().width as f64;
+        let dv = axis.1 * self.base().height as f64;
+        (du * du + dv * dv).sqrt()
+    }
+
+    fn trilinear(&self, u: f64, v: f64, width: f64, wrap: WrapMode) -> Texel {
+        let top = (self.levels.len() - 1) as f64;
+        let lod = if width > 1.0 { width.log2().min(top) } else { 0.0 };
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+
+        let fine = self.levels[level].bilinear(u, v, wrap);
+        if t <= 0.0 || level + 1 >= self.levels.len() {
+            return fine;
+        }
+        lerp(fine, self.levels[level + 1].bilinear(u, v, wrap), t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(size: usize) -> RtwImage {
+        let pixels = (0..size * size)
+            .map(|i| if (i % size + i / size).is_multiple_of(2) { [1.0, 1.0, 1.0, 1.0] } else { [0.0, 0.0, 0.0, 0.0] })
+            .collect();
+        RtwImage::from_pixels(size, size, pixels)
+    }
+
+    #[test]
+    fn test_wrap_index() {
+        assert_eq!(wrap_index(-1, 4, WrapMode::Repeat), 3);
+        assert_eq!(wrap_index(5, 4, WrapMode::Repeat), 1);
+        assert_eq!(wrap_index(-1, 4, WrapMode::Mirror), 0);
+        assert_eq!(wrap_index(5, 4, WrapMode::Mirror), 2);
+        assert_eq!(wrap_index(-3, 4, WrapMode::Clamp), 0);
+        assert_eq!(wrap_index(9, 4, WrapMode::Clamp), 3);
+    }
+
+    #[test]
+    fn test_load_png_with_alpha_and_16_bit() {
+        let dir = std::env::temp_dir();
+
+        let path = dir.join("rtw_test_rgba8.png");
+        let img = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 128]));
+        img.save(&path).unwrap();
+        let loaded = RtwImage::load(&path).unwrap();
+        assert_eq!((loaded.width, loaded.height), (2, 2));
+        let texel = loaded.texel(1, 1);
+        assert!((texel[0] - 1.0).abs() < 1e-6);
+        assert!((texel[3] - 128.0 / 255.0).abs() < 1e-6);
+
+        let path = dir.join("rtw_test_rgb16.png");
+        let img: image::ImageBuffer<image::Rgb<u16>, Vec<u16>> = image::ImageBuffer::from_pixel(1, 1, image::Rgb([65535, 32768, 0]));
+        img.save(&path).unwrap();
+        let texel = RtwImage::load(&path).unwrap().texel(0, 0);
+        assert!((texel[1] - 32768.0 / 65535.0).abs() < 1e-6);
+        assert!((texel[3] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_load_hdr_keeps_range() {
+        let path = std::env::temp_dir().join("rtw_test.hdr");
+        let file = std::fs::File::create(&path).unwrap();
+        let pixels = vec![image::Rgb([8.0f32, 0.5, 0.25]); 2];
+        image::codecs::hdr::HdrEncoder::new(file).encode(&pixels, 2, 1).unwrap();
+        let texel = RtwImage::load(&path).unwrap().texel(0, 0);
+        assert!((texel[0] - 8.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_load_exr_keeps_range() {
+        let path = std::env::temp_dir().join("rtw_test.exr");
+        let img: image::Rgba32FImage = image::ImageBuffer::from_pixel(2, 2, image::Rgba([0.5, 20.0, 0.0, 0.75]));
+        img.save(&path).unwrap();
+        let texel = RtwImage::load(&path).unwrap().texel(1, 0);
+        assert!((texel[1] - 20.0).abs() < 1e-4);
+        assert!((texel[3] - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_missing_file_is_an_error() {
+        assert!(RtwImage::load("definitely/not/here.png").is_err());
+
+        // The texture keeps rendering, in cyan, instead of reporting the error itself.
+        use crate::texture::{ImageTexture, Texture};
+        let c = ImageTexture::new("definitely/not/here.png").value(0.5, 0.5, &Vec3::zero());
+        assert!((c - Color::new(0.0, 1.0, 1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_mipmap_averages_to_mean() {
+        // A large footprint over a checkerboard should come out as flat grey.
+        let mut mipmap = MipMap::new(checker(16));
+        mipmap.build_levels();
+        let c = mipmap.sample(0.3, 0.6, (1.0, 0.0), (0.0, 1.0), TextureFilter::Trilinear, WrapMode::Repeat);
+        assert!((c[0] - 0.5).abs() < 1e-6);
+        assert!((c[3] - 0.5).abs() < 1e-6);
+
+        // A point footprint keeps the texel values.
+        let c = mipmap.sample(0.5 / 16.0, 1.0 - 0.5 / 16.0, (0.0, 0.0), (0.0, 0.0), TextureFilter::Anisotropic(8), WrapMode::Repeat);
+        assert!((c[0] - 1.0).abs() < 1e-6);
+    }
+}
@@ -1,12 +1,29 @@
-use rand;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
 
 // Utility Functions
 
+thread_local! {
+    // Each thread draws from its own generator, seeded randomly unless seed_random is called.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn seed_random(seed: u64) {
+    // Restarts this thread's random numbers from seed, so that building a scene with random parts
+    // gives the same scene every time, in every process.
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn random_u64() -> u64 {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
 pub fn random_double(min: f64, max: f64) -> f64 {
     // Returns a random real in [min,max).
-    min + (max - min) * rand::random::<f64>()
+    min + (max - min) * RNG.with(|rng| rng.borrow_mut().gen::<f64>())
 }
 
 pub fn random_int(min: i32, max: i32) -> i32 {
     // Returns a random integer in [min,max].
-    random_double(min as f64, (max + 1) as f64) as i32}
\ No newline at end of file
+    random_double(min as f64, (max + 1) as f64) as i32}
@@ -0,0 +1,75 @@
+use crate::accumulation::Accumulation;
+use crate::camera::Camera;
+use crate::framebuffer::FrameBuffer;
+use crate::hit_list::HittableList;
+use crate::light::Light;
+use crate::material::Materials;
+use crate::scheduler::Tile;
+use crate::stats::RenderStats;
+use std::sync::Arc;
+
+// Everything needed to render one image.
+pub struct Scene {
+    pub world: HittableList,
+    pub materials: Materials, // Looked up by the MaterialId of every object in world
+    pub camera: Camera,
+    pub extra_targets: HittableList, // Objects worth sampling directly besides the emitters, like glass that focuses light
+    pub delta_lights: Vec<Arc<dyn Light + Send + Sync>>, // Point, spot and directional lights
+}
+
+impl Scene {
+    pub fn new(world: HittableList, materials: Materials, camera: Camera) -> Self {
+        Scene {
+            world,
+            materials,
+            camera,
+            extra_targets: HittableList::new(),
+            delta_lights: Vec::new(),
+        }
+    }
+
+    // Identifies the scene built from a description after seeding the random numbers with seed,
+    // the same in every process and build, to tell apart the samples of different scenes. This is
+    // 64 bit FNV-1a over the seed and the description.
+    pub fn fingerprint(description: &str, seed: u64) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in seed.to_le_bytes().iter().chain(description.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    }
+
+    pub fn with_extra_targets(mut self, extra_targets: HittableList) -> Self {
+        self.extra_targets = extra_targets;
+        self
+    }
+
+    pub fn with_delta_lights(mut self, delta_lights: Vec<Arc<dyn Light + Send + Sync>>) -> Self {
+        self.delta_lights = delta_lights;
+        self
+    }
+
+    pub fn render(&mut self) -> FrameBuffer {
+        self.render_with_progress(|_, _| {})
+    }
+
+    // progress is called from the render threads with the number of pixels done and the total.
+    pub fn render_with_progress(&mut self, progress: impl Fn(usize, usize) + Sync) -> FrameBuffer {
+        self.camera.render(&self.world, &self.materials, &self.extra_targets, &self.delta_lights, &progress)
+    }
+
+    // Renders in the camera's number of passes, calling on_pass with the samples so far after each,
+    // and returns the samples with the statistics of the render.
+    pub fn render_passes(&mut self, progress: impl Fn(usize, usize) + Sync, mut on_pass: impl FnMut(&Accumulation, u32)) -> (Accumulation, RenderStats) {
+        let whole = Tile { x0: 0, x1: self.camera.image_width(), y0: 0, y1: self.camera.image_height() };
+        let renderer = self.camera.renderer(&self.world, &self.materials, &self.extra_targets, &self.delta_lights);
+        let image = renderer.render_passes(&whole, &progress, &mut on_pass);
+        (image, renderer.stats())
+    }
+
+    // Raw samples instead of the averaged image, to save and merge with other renders of the scene.
+    pub fn render_accumulation(&mut self, progress: impl Fn(usize, usize) + Sync) -> Accumulation {
+        self.camera.render_accumulation(&self.world, &self.materials, &self.extra_targets, &self.delta_lights, &progress)
+    }
+}
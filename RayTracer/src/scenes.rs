@@ -0,0 +1,821 @@
+// Scenes from the book and from the features added since, ready to render.
+
+use crate::{bump, bvh, camera, csg, hit_list, light, light_sampler, mapping, material, procedural, quad, rtw, rtweekend, sdf, sphere, texture};
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use crate::material::Materials;
+use crate::hit_list::HittableList;
+use crate::bvh::BVHNode;
+use crate::texture::Texture;
+use crate::quad::make_box;
+use crate::hit::{RotateY, Translate};
+use crate::constant_medium::ConstantMedium;
+use crate::rtweekend::random_double;
+use std::sync::Arc;
+
+// Seed the commands build scenes from, so separate renders of a scene with random parts, like
+// bouncing_spheres, place them the same and their samples merge.
+pub const SCENE_SEED: u64 = 1;
+
+pub fn bouncing_spheres() -> Scene {
+    // World
+    let mut world = hit_list::HittableList::new();
+    let mut materials = Materials::new();
+
+    let ground_material = Some(materials.add(material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rtweekend::random_double(0.0, 1.0);
+            let center = Point3::new(a as f64 + 0.9 * rtweekend::random_double(0.0, 1.0), 0.2, b as f64 + 0.9 * rtweekend::random_double(0.0, 1.0));
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = Vec3::random(0.0, 1.0) * Vec3::random(0.0, 1.0);
+                    let sphere_material = Some(materials.add(material::Lambertian::new(albedo)));
+                    let center2 = center + Vec3::new(0.0, rtweekend::random_double(0.0, 0.5), 0.0);
+                    world.add(Arc::new(sphere::Sphere::new_moving(center, center2, 0.2, sphere_material)));
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = Vec3::random(0.5, 1.0);
+                    let fuzz = rtweekend::random_double(0.0, 0.5);
+                    let sphere_material = Some(materials.add(material::Metal::new(albedo, fuzz)));
+                    world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    // glass
+                    let sphere_material = Some(materials.add(material::Dielectric::new(1.5)));
+                    world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    let material1 = Some(materials.add(material::Dielectric::new(1.5)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material1)));
+
+    let material2 = Some(materials.add(material::Lambertian::new(Vec3::new(0.4, 0.2, 0.1))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material2)));
+
+    let material3 = Some(materials.add(material::Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3)));
+
+
+    let world = HittableList::hittable_list(Arc::new(BVHNode::new(&mut world)));
+
+    let width = 400;
+    let height = 400;
+    // Camera
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(13.0,2.0,3.0);
+    cam.lookat = Point3::new(0.0,0.0,0.0);
+    cam.vup = Vec3::new(0.0,1.0,0.0);
+    cam.defocus_angle = 0.6;
+    cam.focus_dist = 10.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn checkered_spheres() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let checker:Arc<dyn Texture + Send + Sync> = Arc::new(texture::CheckerTexture::from_color(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
+
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -10.0, 0.0), 10.0, Some(materials.add(material::Lambertian::with_texture(checker.clone()))))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 10.0, 0.0), 10.0, Some(materials.add(material::Lambertian::with_texture(checker))))));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(13.0, 2.0, 3.0);
+    cam.lookat = Point3::new(0.0, 0.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn earth() -> Scene {
+    let mut materials = Materials::new();
+    let earth_texture = Arc::new(texture::ImageTexture::new("earthmap.jpg"));
+    let earth_surface = Some(materials.add(material::Lambertian::with_texture(earth_texture)));
+    let globe = Arc::new(sphere::Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, earth_surface));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(0.0, 0.0, 12.0);
+    cam.lookat = Point3::new(0.0, 0.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(HittableList::hittable_list(globe), materials, cam)
+}
+
+pub fn perlin_spheres() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let pertext: Arc<dyn Texture + Send + Sync> = Arc::new(texture::NoiseTexture::with_scale(4.0));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, Some(materials.add(material::Lambertian::with_texture(pertext.clone()))))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 2.0, 0.0), 2.0, Some(materials.add(material::Lambertian::with_texture(pertext))))));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(13.0, 2.0, 3.0);
+    cam.lookat = Point3::new(0.0, 0.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn quads() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    // Materials
+    let left_red = Some(materials.add(material::Lambertian::new(Vec3::new(1.0, 0.2, 0.2))));
+    let back_green = Some(materials.add(material::Lambertian::new(Vec3::new(0.2, 1.0, 0.2))));
+    let right_blue = Some(materials.add(material::Lambertian::new(Vec3::new(0.2, 0.2, 1.0))));
+    let upper_orange = Some(materials.add(material::Lambertian::new(Vec3::new(1.0, 0.5, 0.0))));
+    let lower_teal = Some(materials.add(material::Lambertian::new(Vec3::new(0.2, 0.8, 0.8))));
+
+    // Quads
+    world.add(Arc::new(quad::Quad::new(Point3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0), upper_orange)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -4.0), lower_teal)));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+
+    cam.vfov = 80.0;
+    cam.lookfrom = Point3::new(0.0, 0.0, 9.0);
+    cam.lookat = Point3::new(0.0, 0.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn simple_light() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let pertext: Arc<dyn Texture + Send + Sync> = Arc::new(texture::NoiseTexture::with_scale(4.0));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, Some(materials.add(material::Lambertian::with_texture(pertext.clone()))))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 2.0, 0.0), 2.0, Some(materials.add(material::Lambertian::with_texture(pertext))))));
+
+    let difflight = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(4.0, 4.0, 4.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 7.0, 0.0), 2.0, difflight)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight)));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(26.0, 3.0, 6.0);
+    cam.lookat = Point3::new(0.0, 2.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+    Scene::new(world, materials, cam)
+}
+
+pub fn cornell_box(integrator: camera::Integrator) -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let red = Some(materials.add(material::Lambertian::new(Vec3::new(0.65, 0.05, 0.05))));
+    let white = Some(materials.add(material::Lambertian::new(Vec3::new(0.73, 0.73, 0.73))));
+    let green = Some(materials.add(material::Lambertian::new(Vec3::new(0.12, 0.45, 0.15))));
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(15.0, 15.0, 15.0))));
+
+    world.add(Arc::new(quad::Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white)));
+
+    // world.addlist(make_box(Point3::new(130.0, 0.0, 65.0), Point3::new(295.0, 165.0, 230.0), white.clone()));
+    // world.addlist(make_box(Point3::new(265.0, 0.0, 295.0), Point3::new(430.0, 330.0, 460.0), white));
+
+    // let aluminum = Some(materials.add(material::Metal::new(Vec3::new(0.8, 0.85, 0.88), 0.0)));
+    // let box1 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), aluminum));
+    // let box1 = Arc::new(RotateY::new(box1, 15.0));
+    // let box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+    // world.add(box1);
+
+    // let box2 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white.clone()));
+    // let box2 = Arc::new(RotateY::new(box2, -18.0));
+    // let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+    // world.add(box2);
+
+    // Box
+    let box1 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white));
+    let box1 = Arc::new(RotateY::new(box1, 15.0));
+    let box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+    world.add(box1);
+
+    // Glass Sphere
+    let glass = Some(materials.add(material::Dielectric::new(1.5)));
+    let glass_sphere = Arc::new(sphere::Sphere::new(Point3::new(190.0, 90.0, 190.0), 90.0, glass));
+    world.add(glass_sphere.clone());
+
+    // The light is found automatically; the glass sphere is worth sampling directly too
+    let lights = HittableList::hittable_list(glass_sphere);
+
+    let width = 400;
+    let height = 400;
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = 1000;
+    cam.max_depth = 50;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(278.0, 278.0, -800.0);
+    cam.lookat = Point3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+
+    cam.integrator = integrator;
+    if integrator == camera::Integrator::Bidirectional {
+        // Every pair of path vertices is connected, so fewer and shorter paths take about as long.
+        cam.samples_per_pixel = 100;
+        cam.max_depth = 10;
+    }
+
+    Scene::new(world, materials, cam).with_extra_targets(lights)
+}
+
+pub fn cornell_smoke() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let red = Some(materials.add(material::Lambertian::new(Vec3::new(0.65, 0.05, 0.05))));
+    let white = Some(materials.add(material::Lambertian::new(Vec3::new(0.73, 0.73, 0.73))));
+    let green = Some(materials.add(material::Lambertian::new(Vec3::new(0.12, 0.45, 0.15))));
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(7.0, 7.0, 7.0))));
+
+    world.add(Arc::new(quad::Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white)));
+
+    let box1 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white));
+    let box1 = Arc::new(RotateY::new(box1, 15.0));
+    let box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+
+    let box2 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white));
+    let box2 = Arc::new(RotateY::new(box2, -18.0));
+    let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+
+    world.add(Arc::new(ConstantMedium::new(box1, 0.01, materials.add(material::Isotropic::new(Color::new(0.0, 0.0, 0.0))))));
+    world.add(Arc::new(ConstantMedium::new(box2, 0.01, materials.add(material::Isotropic::new(Color::new(1.0, 1.0, 1.0))))));
+
+    let height = 400;
+    let width = 400;
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = 200;
+    cam.max_depth = 50;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(278.0, 278.0, -800.0);
+    cam.lookat = Point3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+
+    Scene::new(world, materials, cam)
+}
+
+// The 400 boxes of varying height that make the ground of final_scene.
+pub fn final_scene_boxes(materials: &mut Materials) -> HittableList {
+    let mut boxes1 = hit_list::HittableList::new();
+    let ground = Some(materials.add(material::Lambertian::new(Vec3::new(0.48, 0.83, 0.53))));
+
+    let boxes_per_side = 20;
+    for i in 0..boxes_per_side{
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y0 = 0.0;
+            let x1 = x0 + w;
+            let y1 = random_double(1.0, 101.0);
+            let z1 = z0 + w;
+
+            boxes1.addlist(make_box(Point3::new(x0,y0,z0), Point3::new(x1,y1,z1), ground));
+        }
+    }
+    boxes1
+}
+
+// The cube of 1000 small white spheres in final_scene, before it is rotated and moved into place.
+pub fn final_scene_spheres(materials: &mut Materials) -> HittableList {
+    let mut boxes2 = hit_list::HittableList::new();
+    let white = Some(materials.add(material::Lambertian::new(Vec3::new(0.73, 0.73, 0.73))));
+    let ns = 1000;
+
+    for _ in 0..ns {
+        boxes2.add(Arc::new(sphere::Sphere::new(Vec3::random(0.0, 165.0), 10.0, white)));
+    }
+    boxes2
+}
+
+pub fn final_scene(height: u32, width: u32, samples_per_pixel: u32, max_depth: u32) -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+    let mut boxes1 = final_scene_boxes(&mut materials);
+    world.add(Arc::new(bvh::BVHNode::new(&mut boxes1)));
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(7.0, 7.0, 7.0))));
+    world.add(Arc::new(quad::Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), light)));
+
+    let center1 = Point3::new(400.0, 400.0, 200.0);
+    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
+    let sphere_material = materials.add(material::Lambertian::new(Vec3::new(0.7, 0.3, 0.1)));
+    world.add(Arc::new(sphere::Sphere::new_moving(center1, center2, 50.0, Some(sphere_material))));
+
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(260.0, 150.0, 45.0), 50.0, Some(materials.add(material::Dielectric::new(1.5))))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 150.0, 145.0), 50.0, Some(materials.add(material::Metal::new(Vec3::new(0.8, 0.8, 0.9), 1.0))))));
+
+    let boundary = Arc::new(sphere::Sphere::new(Point3::new(360.0, 150.0, 145.0), 70.0, Some(materials.add(material::Dielectric::new(1.5)))));
+    world.add(boundary.clone());
+    world.add(Arc::new(ConstantMedium::new(boundary.clone(), 0.2, materials.add(material::Isotropic::new(Color::new(0.2, 0.4, 0.9))))));
+    let boundary = Arc::new(sphere::Sphere::new(Point3::new(0.0, 0.0, 0.0), 5000.0, Some(materials.add(material::Dielectric::new(1.5)))));
+    world.add(Arc::new(ConstantMedium::new(boundary.clone(), 0.0001, materials.add(material::Isotropic::new(Color::new(1.0, 1.0, 1.0))))));
+
+    let emat = Some(materials.add(material::Lambertian::with_texture(Arc::new(texture::ImageTexture::new("earthmap.jpg")))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(400.0, 200.0, 400.0), 100.0, emat)));
+
+    let pertext = Some(materials.add(material::Lambertian::with_texture(Arc::new(texture::NoiseTexture::with_scale(0.2)))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(220.0, 280.0, 300.0), 80.0, pertext)));
+
+    let mut boxes2 = final_scene_spheres(&mut materials);
+    world.add(Arc::new(Translate::new(
+        Arc::new(RotateY::new(
+            Arc::new(bvh::BVHNode::new(&mut boxes2)),
+            15.0,
+        )),
+        Vec3::new(-100.0, 270.0, 395.0),
+    )));
+
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = samples_per_pixel;
+    cam.max_depth = max_depth;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(478.0, 278.0, -600.0);
+    cam.lookat = Point3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn sdf_shapes() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let ground = Arc::new(texture::CheckerTexture::from_color(0.5, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, Some(materials.add(material::Lambertian::with_texture(ground))))));
+
+    // Two spheres melted into one blob
+    let blob = Arc::new(sdf::SmoothUnion::new(
+        Arc::new(sdf::SdfSphere::new(Point3::new(-2.4, 0.8, 0.0), 0.8)),
+        Arc::new(sdf::SdfSphere::new(Point3::new(-1.4, 1.2, 0.3), 0.6)),
+        0.5,
+    ));
+    let blob_material = Some(materials.add(material::Lambertian::new(Color::new(0.8, 0.3, 0.2))));
+    world.add(Arc::new(sdf::SdfShape::new(blob, blob_material)));
+
+    let rounded_box = Arc::new(sdf::SdfBox::rounded(Point3::new(0.0, 0.7, 0.0), Vec3::new(0.7, 0.7, 0.7), 0.15));
+    let glass = Some(materials.add(material::Dielectric::new(1.5)));
+    world.add(Arc::new(sdf::SdfShape::new(rounded_box, glass)));
+
+    let torus = Arc::new(sdf::SdfTorus::new(Point3::new(2.2, 0.3, 0.0), 0.8, 0.3));
+    let gold = Some(materials.add(material::Metal::new(Color::new(0.8, 0.6, 0.2), 0.1)));
+    world.add(Arc::new(sdf::SdfShape::new(torus, gold)));
+
+    // A row of small beads behind everything, made from a single repeated sphere
+    let bead = Arc::new(sdf::SdfSphere::new(Point3::new(0.0, 0.0, 0.0), 0.2));
+    let beads = Arc::new(sdf::Repeat::new(bead, Vec3::new(0.6, 0.0, 0.0), [6, 0, 0]));
+    let beads = Arc::new(Translate::new(Arc::new(sdf::SdfShape::new(beads, Some(materials.add(material::Lambertian::new(Color::new(0.73, 0.73, 0.73)))))), Vec3::new(0.0, 0.2, -2.0)));
+    world.add(beads);
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(6.0, 6.0, 6.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 8.0, 4.0), 2.0, light)));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 30.0;
+    cam.lookfrom = Point3::new(0.0, 3.0, 10.0);
+    cam.lookat = Point3::new(0.0, 0.6, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.1, 0.12, 0.16);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn csg_shapes() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let red = Some(materials.add(material::Lambertian::new(Vec3::new(0.65, 0.05, 0.05))));
+    let white = Some(materials.add(material::Lambertian::new(Vec3::new(0.73, 0.73, 0.73))));
+    let green = Some(materials.add(material::Lambertian::new(Vec3::new(0.12, 0.45, 0.15))));
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(15.0, 15.0, 15.0))));
+
+    world.add(Arc::new(quad::Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white)));
+    world.add(Arc::new(quad::Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white)));
+
+    // Box with a spherical bite taken out of its top corner
+    let block = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white));
+    let bite = Arc::new(sphere::Sphere::new(Point3::new(165.0, 165.0, 0.0), 100.0, red));
+    let block = Arc::new(csg::Csg::difference(block, bite));
+    let block = Arc::new(RotateY::new(block, -18.0));
+    world.add(Arc::new(Translate::new(block, Vec3::new(130.0, 0.0, 65.0))));
+
+    // Glass lens from two overlapping spheres
+    let glass = Some(materials.add(material::Dielectric::new(1.5)));
+    let lens = csg::Csg::intersection(
+        Arc::new(sphere::Sphere::new(Point3::new(330.0, 250.0, 150.0), 120.0, glass)),
+        Arc::new(sphere::Sphere::new(Point3::new(330.0, 250.0, 330.0), 120.0, glass)),
+    );
+    world.add(Arc::new(lens));
+
+    // Smoke filling the union of two spheres
+    let cloud = Arc::new(csg::Csg::union(
+        Arc::new(sphere::Sphere::new(Point3::new(380.0, 90.0, 380.0), 90.0, white)),
+        Arc::new(sphere::Sphere::new(Point3::new(450.0, 150.0, 380.0), 70.0, white)),
+    ));
+    world.add(Arc::new(ConstantMedium::new(cloud, 0.02, materials.add(material::Isotropic::new(Color::new(0.2, 0.4, 0.9))))));
+
+    let width = 400;
+    let height = 400;
+    let mut cam = camera::Camera::new(height, width);
+
+    cam.samples_per_pixel = 400;
+    cam.max_depth = 50;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(278.0, 278.0, -800.0);
+    cam.lookat = Point3::new(278.0, 278.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.0;
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn tiled_floor() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    // A large floor with the earth map tiled across it, filtered so the far tiles do not shimmer
+    let tiles = texture::ImageTexture::new("earthmap.jpg")
+        .with_filter(rtw::TextureFilter::Anisotropic(16))
+        .with_wrap(rtw::WrapMode::Repeat)
+        .with_uv_transform((40.0, 40.0), (0.0, 0.0));
+    let floor = Some(materials.add(material::Lambertian::with_texture(Arc::new(tiles))));
+    world.add(Arc::new(quad::Quad::new(Point3::new(-200.0, 0.0, -200.0), Vec3::new(400.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 400.0), floor)));
+
+    let globe = texture::ImageTexture::new("earthmap.jpg").with_filter(rtw::TextureFilter::Trilinear);
+    let globe = Some(materials.add(material::Lambertian::with_texture(Arc::new(globe))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, globe)));
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(10.0, 10.0, 10.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(-10.0, 20.0, 10.0), 5.0, light)));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(0.0, 1.5, 6.0);
+    cam.lookat = Point3::new(0.0, 0.8, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn bumpy_spheres() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    // Flat-coloured spheres whose surface detail comes only from their shading normals
+    let ground = Some(materials.add(material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+    let noise: Arc<dyn Texture + Send + Sync> = Arc::new(texture::NoiseTexture::with_scale(4.0));
+    let ground_bumps = Arc::new(bump::BumpMap::new(noise.clone(), 0.02));
+    world.add(Arc::new(bump::Bumped::new(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground)), ground_bumps)));
+
+    let clay = Some(materials.add(material::Lambertian::new(Vec3::new(0.8, 0.4, 0.3))));
+    let clay_bumps = Arc::new(bump::BumpMap::new(noise.clone(), 0.1));
+    world.add(Arc::new(bump::Bumped::new(Arc::new(sphere::Sphere::new(Point3::new(0.0, 2.0, -2.5), 2.0, clay)), clay_bumps)));
+
+    let hammered = Some(materials.add(material::Metal::new(Vec3::new(0.8, 0.8, 0.9), 0.05)));
+    let hammered_bumps = Arc::new(bump::BumpMap::new(noise, 0.04));
+    world.add(Arc::new(bump::Bumped::new(Arc::new(sphere::Sphere::new(Point3::new(0.0, 2.0, 2.5), 2.0, hammered)), hammered_bumps)));
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(10.0, 10.0, 10.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(10.0, 20.0, 5.0), 5.0, light)));
+
+    let width = 400;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 30.0;
+    cam.lookfrom = Point3::new(13.0, 3.0, 0.0);
+    cam.lookat = Point3::new(0.0, 1.5, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn procedural_textures() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    // A row of spheres, one per procedural texture, on a cellular floor
+    let cells = procedural::WorleyTexture::new(1)
+        .with_feature(procedural::WorleyFeature::F2MinusF1)
+        .with_ramp(procedural::ColorRamp::two(Color::new(0.1, 0.1, 0.1), Color::new(0.8, 0.8, 0.7)));
+    let floor = Some(materials.add(material::Lambertian::with_texture(Arc::new(cells))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, floor)));
+
+    let clouds = procedural::FractalTexture::new(procedural::Fractal::new(2).with_octaves(8).with_gain(0.55))
+        .with_scale(2.0)
+        .with_ramp(procedural::ColorRamp::two(Color::new(0.2, 0.35, 0.8), Color::new(1.0, 1.0, 1.0)));
+    let mountains = procedural::FractalTexture::new(procedural::Fractal::new(3).with_kind(procedural::FractalKind::Ridged))
+        .with_scale(2.0)
+        .with_ramp(procedural::ColorRamp::new(vec![
+            (0.3, Color::new(0.2, 0.3, 0.1)),
+            (0.7, Color::new(0.45, 0.4, 0.35)),
+            (0.9, Color::new(0.95, 0.95, 0.95)),
+        ]));
+    let wood = procedural::WoodTexture::new(4).with_scale(2.0);
+    let marble = procedural::MarbleTexture::new(5)
+        .with_scale(4.0)
+        .with_ramp(procedural::ColorRamp::two(Color::new(0.15, 0.2, 0.2), Color::new(0.95, 0.95, 0.9)));
+
+    let textures: Vec<Arc<dyn Texture + Send + Sync>> = vec![Arc::new(clouds), Arc::new(mountains), Arc::new(wood), Arc::new(marble)];
+    for (i, tex) in textures.into_iter().enumerate() {
+        let mat = Some(materials.add(material::Lambertian::with_texture(tex)));
+        world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, -3.3 + 2.2 * i as f64), 1.0, mat)));
+    }
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(10.0, 10.0, 10.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(10.0, 20.0, 5.0), 5.0, light)));
+
+    let width = 600;
+    let height = 300;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 30.0;
+    cam.lookfrom = Point3::new(13.0, 3.0, 0.0);
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn texture_mapping() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    // UV checkers follow the sphere's surface instead of slicing through it
+    let checker = texture::UvCheckerTexture::from_color((16.0, 8.0), Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+    let checker = Some(materials.add(material::Lambertian::with_texture(Arc::new(checker))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, -2.5), 1.0, checker)));
+
+    // The earth map wrapped around a box, which has no uv of its own that would suit it
+    let earth: Arc<dyn Texture + Send + Sync> = Arc::new(texture::ImageTexture::new("earthmap.jpg").with_wrap(rtw::WrapMode::Repeat));
+    let wrapped = mapping::MappedTexture::new(earth.clone(), mapping::Projection::Cylindrical)
+        .with_transform(mapping::Transform3::new().with_scale(Vec3::new(1.0, 0.5, 1.0)));
+    let wrapped = Some(materials.add(material::Lambertian::with_texture(Arc::new(wrapped))));
+    world.add(Arc::new(make_box(Point3::new(-0.8, 0.0, -0.8), Point3::new(0.8, 2.0, 0.8), wrapped)));
+
+    // Triplanar wood on a rounded box, and a rotated planar projection on the floor
+    let wood = mapping::TriplanarTexture::new(Arc::new(procedural::WoodTexture::new(6)))
+        .with_transform(mapping::Transform3::new().with_offset(Vec3::new(0.0, -1.0, -2.5)).with_scale(Vec3::new(1.5, 1.5, 1.5)));
+    let wood = Some(materials.add(material::Lambertian::with_texture(Arc::new(wood))));
+    let rounded = sdf::SdfBox::rounded(Point3::new(0.0, 1.0, 2.5), Vec3::new(0.9, 0.9, 0.9), 0.2);
+    world.add(Arc::new(sdf::SdfShape::new(Arc::new(rounded), wood)));
+
+    let floor = mapping::MappedTexture::new(earth, mapping::Projection::Planar)
+        .with_transform(mapping::Transform3::new().with_rotation(Vec3::new(1.0, 0.0, 0.0), 90.0))
+        .with_uv_transform(mapping::Transform2::new().with_scale(0.1, 0.2).with_rotation(30.0));
+    let floor = Some(materials.add(material::Lambertian::with_texture(Arc::new(floor))));
+    world.add(Arc::new(quad::Quad::new(Point3::new(-20.0, 0.0, -20.0), Vec3::new(40.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 40.0), floor)));
+
+    let light = Some(materials.add(material::DiffuseLight::with_color(Vec3::new(10.0, 10.0, 10.0))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(10.0, 20.0, 5.0), 5.0, light)));
+
+    let width = 600;
+    let height = 300;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 30.0;
+    cam.lookfrom = Point3::new(13.0, 4.0, 0.0);
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.70, 0.80, 1.00);
+
+    Scene::new(world, materials, cam)
+}
+
+pub fn delta_lights() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let ground = Some(materials.add(material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let red = Some(materials.add(material::Lambertian::new(Vec3::new(0.7, 0.2, 0.2))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, -2.5), 1.0, red)));
+    let glass = Some(materials.add(material::Dielectric::new(1.5)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, glass)));
+    let metal = Some(materials.add(material::Metal::new(Vec3::new(0.8, 0.8, 0.9), 0.2)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, 2.5), 1.0, metal)));
+
+    // Nothing emissive to hit: all of the light comes from the delta lights
+    let lights = hit_list::HittableList::new();
+    let delta_lights: Vec<Arc<dyn light::Light + Send + Sync>> = vec![
+        Arc::new(light::DirectionalLight::new(Vec3::new(-1.0, -2.0, -0.5), Color::new(0.6, 0.55, 0.45))),
+        Arc::new(light::SpotLight::new(Point3::new(4.0, 6.0, -2.5), Point3::new(0.0, 0.0, -2.5), Color::new(40.0, 40.0, 60.0), 10.0, 20.0)),
+        Arc::new(light::PointLight::new(Point3::new(3.0, 2.5, 4.0), Color::new(15.0, 10.0, 5.0))),
+    ];
+
+    let width = 600;
+    let height = 300;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 30.0;
+    cam.lookfrom = Point3::new(13.0, 3.0, 0.0);
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.05, 0.05, 0.08);
+
+    Scene::new(world, materials, cam).with_extra_targets(lights).with_delta_lights(delta_lights)
+}
+
+pub fn many_lights() -> Scene {
+    let mut materials = Materials::new();
+    let mut world = hit_list::HittableList::new();
+
+    let ground = Some(materials.add(material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    // A field of 400 small coloured lamps of random brightness hanging over some spheres
+    let mut lamps = hit_list::HittableList::new();
+    for a in -10..10 {
+        for b in -10..10 {
+            let color = Vec3::random(0.2, 1.0) * random_double(1.0, 20.0);
+            let lamp = Some(materials.add(material::DiffuseLight::with_color(color)));
+            let center = Point3::new(a as f64 + random_double(0.0, 0.5), random_double(0.5, 3.0), b as f64 + random_double(0.0, 0.5));
+            lamps.add(Arc::new(sphere::Sphere::new(center, 0.05, lamp)));
+        }
+    }
+    world.add(Arc::new(BVHNode::new(&mut lamps)));
+
+    let white = Some(materials.add(material::Lambertian::new(Vec3::new(0.73, 0.73, 0.73))));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, white)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(-3.0, 0.7, 2.0), 0.7, white)));
+    let metal = Some(materials.add(material::Metal::new(Vec3::new(0.8, 0.8, 0.9), 0.1)));
+    world.add(Arc::new(sphere::Sphere::new(Point3::new(3.0, 0.7, -2.0), 0.7, metal)));
+
+    let width = 600;
+    let height = 400;
+
+    let mut cam = camera::Camera::new(height, width);
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+    cam.vfov = 40.0;
+    cam.lookfrom = Point3::new(12.0, 5.0, 6.0);
+    cam.lookat = Point3::new(0.0, 1.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Vec3::new(0.0, 0.0, 0.0);
+    cam.light_sampling = light_sampler::LightSampling::Bvh;
+
+    Scene::new(world, materials, cam)
+}
+
+// Builds a scene from its name and arguments, such as "final_scene 800 800 10000 40" or
+// "cornell_box bidirectional", for passing scenes between processes as text.
+pub fn from_description(description: &str) -> Option<Scene> {
+    let words: Vec<&str> = description.split_whitespace().collect();
+    let numbers: Vec<u32> = words.iter().skip(1).filter_map(|word| word.parse().ok()).collect();
+    let scene = match words.as_slice() {
+        ["bouncing_spheres"] => bouncing_spheres(),
+        ["checkered_spheres"] => checkered_spheres(),
+        ["earth"] => earth(),
+        ["perlin_spheres"] => perlin_spheres(),
+        ["quads"] => quads(),
+        ["simple_light"] => simple_light(),
+        ["cornell_box"] | ["cornell_box", "path"] => cornell_box(camera::Integrator::PathTracing),
+        ["cornell_box", "bidirectional"] => cornell_box(camera::Integrator::Bidirectional),
+        ["cornell_box", "photons"] => cornell_box(camera::Integrator::PhotonMapping { photons: 2_000_000, nearest: 100, radius: 20.0 }),
+        ["cornell_smoke"] => cornell_smoke(),
+        ["final_scene", _, _, _, _] if numbers.len() == 4 => final_scene(numbers[0], numbers[1], numbers[2], numbers[3]),
+        ["sdf_shapes"] => sdf_shapes(),
+        ["csg_shapes"] => csg_shapes(),
+        ["tiled_floor"] => tiled_floor(),
+        ["bumpy_spheres"] => bumpy_spheres(),
+        ["procedural_textures"] => procedural_textures(),
+        ["texture_mapping"] => texture_mapping(),
+        ["delta_lights"] => delta_lights(),
+        ["many_lights"] => many_lights(),
+        _ => return None,
+    };
+    Some(scene)
+}
+
+// A grey light filling the view of any camera at the origin looking down -z, so every sample comes
+// out as 0.5. For tests of the rendering machinery rather than of the light transport.
+#[cfg(test)]
+pub(crate) fn glowing_wall() -> (HittableList, Materials) {
+    let mut materials = Materials::new();
+    let light = Some(materials.add(material::DiffuseLight::with_color(Color::new(0.5, 0.5, 0.5))));
+    let mut world = HittableList::new();
+    world.add(Arc::new(quad::Quad::new(Point3::new(-10.0, -10.0, -1.0), Vec3::new(20.0, 0.0, 0.0), Vec3::new(0.0, 20.0, 0.0), light)));
+    (world, materials)
+}
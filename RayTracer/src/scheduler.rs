@@ -0,0 +1,135 @@
+// Order in which image tiles are handed out to the render threads.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TileOrder {
+    Scanline, // Rows of tiles from the top left
+    Spiral, // Rings of tiles from the centre outwards, so the middle of the image shows up first
+    Hilbert, // Along a Hilbert curve, so that tiles rendered close in time are close in the image
+}
+
+// Rectangle of pixels [x0, x1) x [y0, y1) rendered as one job.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tile {
+    pub x0: u32,
+    pub x1: u32,
+    pub y0: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width() as usize * self.height() as usize
+    }
+}
+
+// Splits the image into tiles of at most tile_size pixels square, listed in the given order.
+pub fn tiles(image_width: u32, image_height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let columns = image_width.div_ceil(tile_size);
+    let rows = image_height.div_ceil(tile_size);
+
+    let mut cells: Vec<(u32, u32)> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let cx = (columns as f64 - 1.0) / 2.0;
+            let cy = (rows as f64 - 1.0) / 2.0;
+            let key = |&(column, row): &(u32, u32)| {
+                let dx = column as f64 - cx;
+                let dy = row as f64 - cy;
+                (dx.abs().max(dy.abs()).round(), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| {
+                let (ring_a, angle_a) = key(a);
+                let (ring_b, angle_b) = key(b);
+                ring_a.total_cmp(&ring_b).then(angle_a.total_cmp(&angle_b))
+            });
+        }
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(column, row)| hilbert_index(n, column, row));
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|(column, row)| Tile {
+            x0: column * tile_size,
+            x1: ((column + 1) * tile_size).min(image_width),
+            y0: row * tile_size,
+            y1: ((row + 1) * tile_size).min(image_height),
+        })
+        .collect()
+}
+
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    // Distance along the Hilbert curve filling an n x n grid, n a power of two, to cell x, y.
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        d += u64::from(s) * u64::from(s) * u64::from((3 * rx) ^ ry);
+
+        // Rotate the quadrant so the curve inside it runs the standard way.
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_covers(tiles: &[Tile], width: u32, height: u32) {
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in tiles {
+            for j in tile.y0..tile.y1 {
+                for i in tile.x0..tile.x1 {
+                    covered[(j * width + i) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn test_every_order_covers_image_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let tiles = tiles(37, 23, 8, order);
+            assert_eq!(tiles.len(), 5 * 3);
+            assert_covers(&tiles, 37, 23);
+        }
+    }
+
+    #[test]
+    fn test_hilbert_steps_to_neighbours() {
+        let tiles = tiles(64, 64, 8, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x0 as i64 - pair[1].x0 as i64).abs();
+            let dy = (pair[0].y0 as i64 - pair[1].y0 as i64).abs();
+            assert_eq!(dx + dy, 8);
+        }
+    }
+
+    #[test]
+    fn test_spiral_starts_in_centre() {
+        let tiles = tiles(50, 50, 10, TileOrder::Spiral);
+        assert_eq!(tiles[0], Tile { x0: 20, x1: 30, y0: 20, y1: 30 });
+        assert_eq!(tiles.len(), 25);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::hit::{HitRecord, Hittable, HittableClone};
+use crate::vec3::Vec3;
+type Point3 = Vec3;
+use crate::aabb::AABB;
+use crate::interval::Interval;
+use crate::material::MaterialId;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::stats::{count, Counter};
+use std::sync::Arc;
+
+const MAX_STEPS: u32 = 256;
+const HIT_EPSILON: f64 = 1e-4;
+const NORMAL_EPSILON: f64 = 1e-5;
+
+pub trait Sdf {
+    // Signed distance from p to the surface: negative inside, positive outside.
+    // The value must never overestimate the true distance, or the tracer can step through the surface.
+    fn distance(&self, p: &Point3) -> f64;
+    fn bounding_box(&self) -> AABB;
+}
+
+fn abs_vec(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+fn max_vec(v: Vec3, m: f64) -> Vec3 {
+    Vec3::new(v.x.max(m), v.y.max(m), v.z.max(m))
+}
+
+#[derive(Clone)]
+pub struct SdfSphere {
+    center: Point3,
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(center: Point3, radius: f64) -> Self {
+        SdfSphere { center, radius: f64::max(0.0, radius) }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Point3) -> f64 {
+        (*p - self.center).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        AABB::from_points(self.center - rvec, self.center + rvec)
+    }
+}
+
+#[derive(Clone)]
+pub struct SdfBox {
+    center: Point3,
+    half_size: Vec3,
+    radius: f64, // Corner rounding radius, zero for a sharp box
+}
+
+impl SdfBox {
+    pub fn new(center: Point3, half_size: Vec3) -> Self {
+        SdfBox { center, half_size, radius: 0.0 }
+    }
+
+    // Box whose edges are rounded off with the given radius; the outer extent stays half_size.
+    pub fn rounded(center: Point3, half_size: Vec3, radius: f64) -> Self {
+        let min_half = half_size.x.min(half_size.y).min(half_size.z);
+        SdfBox { center, half_size, radius: radius.clamp(0.0, min_half) }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = abs_vec(*p - self.center) - self.half_size + self.radius;
+        max_vec(q, 0.0).length() + q.x.max(q.y).max(q.z).min(0.0) - self.radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        AABB::from_points(self.center - self.half_size, self.center + self.half_size)
+    }
+}
+
+#[derive(Clone)]
+pub struct SdfTorus {
+    center: Point3,
+    major_radius: f64, // Distance from the center to the middle of the tube
+    minor_radius: f64, // Radius of the tube
+}
+
+impl SdfTorus {
+    // Torus lying in the xz-plane around the y axis.
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64) -> Self {
+        SdfTorus { center, major_radius, minor_radius }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = *p - self.center;
+        let ring = (q.x * q.x + q.z * q.z).sqrt() - self.major_radius;
+        (ring * ring + q.y * q.y).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let r = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(r, self.minor_radius, r);
+        AABB::from_points(self.center - extent, self.center + extent)
+    }
+}
+
+#[derive(Clone)]
+pub struct SmoothUnion {
+    a: Arc<dyn Sdf + Send + Sync>,
+    b: Arc<dyn Sdf + Send + Sync>,
+    k: f64, // Blend radius, zero gives a hard union
+}
+
+impl SmoothUnion {
+    pub fn new(a: Arc<dyn Sdf + Send + Sync>, b: Arc<dyn Sdf + Send + Sync>, k: f64) -> Self {
+        SmoothUnion { a, b, k: f64::max(0.0, k) }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: &Point3) -> f64 {
+        // Polynomial smooth minimum.
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        if self.k <= 0.0 {
+            return d1.min(d2);
+        }
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        d2 * (1.0 - h) + d1 * h - self.k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        // The blend grows the shape by at most k/4 beyond the plain union, well inside this padding.
+        let bbox = AABB::from_aabbs(&self.a.bounding_box(), &self.b.bounding_box());
+        AABB::from_intervals(bbox.x.expand(self.k), bbox.y.expand(self.k), bbox.z.expand(self.k))
+    }
+}
+
+#[derive(Clone)]
+pub struct Repeat {
+    sdf: Arc<dyn Sdf + Send + Sync>,
+    period: Vec3,
+    count: [i32; 3], // Number of extra copies on each side of the original, per axis
+}
+
+impl Repeat {
+    // Repeats the shape on a grid around the origin; the shape should be centered at the origin
+    // and fit inside one cell so that neighbouring copies do not break the distance bound.
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, period: Vec3, count: [i32; 3]) -> Self {
+        Repeat {
+            sdf,
+            period,
+            count: [count[0].max(0), count[1].max(0), count[2].max(0)],
+        }
+    }
+
+    fn cell(&self, x: f64, period: f64, count: i32) -> f64 {
+        if period <= 0.0 {
+            return x;
+        }
+        x - period * (x / period).round().clamp(-count as f64, count as f64)
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = Point3::new(
+            self.cell(p.x, self.period.x, self.count[0]),
+            self.cell(p.y, self.period.y, self.count[1]),
+            self.cell(p.z, self.period.z, self.count[2]),
+        );
+        self.sdf.distance(&q)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let bbox = self.sdf.bounding_box();
+        let extent = Vec3::new(
+            self.period.x * self.count[0] as f64,
+            self.period.y * self.count[1] as f64,
+            self.period.z * self.count[2] as f64,
+        );
+        AABB::from_aabbs(&(bbox + -1.0 * extent), &(bbox + extent))
+    }
+}
+
+#[derive(Clone)]
+pub struct SdfShape {
+    sdf: Arc<dyn Sdf + Send + Sync>,
+    mat: Option<MaterialId>,
+    bbox: AABB,
+    max_steps: u32,
+    epsilon: f64,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, mat: Option<MaterialId>) -> Self {
+        let bbox = sdf.bounding_box();
+        SdfShape {
+            sdf,
+            mat,
+            bbox,
+            max_steps: MAX_STEPS,
+            epsilon: HIT_EPSILON,
+        }
+    }
+
+    pub fn with_precision(mut self, max_steps: u32, epsilon: f64) -> Self {
+        self.max_steps = max_steps;
+        self.epsilon = epsilon;
+        self
+    }
+
+    fn gradient(&self, p: &Point3) -> Vec3 {
+        // Central differences of the distance field.
+        let e = NORMAL_EPSILON;
+        let dx = Vec3::new(e, 0.0, 0.0);
+        let dy = Vec3::new(0.0, e, 0.0);
+        let dz = Vec3::new(0.0, 0.0, e);
+        Vec3::new(
+            self.sdf.distance(&(*p + dx)) - self.sdf.distance(&(*p - dx)),
+            self.sdf.distance(&(*p + dy)) - self.sdf.distance(&(*p - dy)),
+            self.sdf.distance(&(*p + dz)) - self.sdf.distance(&(*p - dz)),
+        )
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        count(Counter::PrimitiveTest);
+        // Only march through the part of the ray inside the bounding box.
+        let span = match self.bbox.clip(r, ray_t) {
+            Some(span) => span,
+            None => return false,
+        };
+
+        let dir_length = r.direction().length();
+        let mut t = span.min;
+        let start = r.at(t);
+        let d0 = self.sdf.distance(&start);
+
+        // March on whichever side of the surface the ray starts on. A ray that starts on the
+        // surface (e.g. a scattered ray) must first get clear of it before a hit counts; a ray
+        // entering the bounding box from outside is always outside the shape.
+        let entered = span.min > ray_t.min;
+        let mut side = if d0 < 0.0 && !entered { -1.0 } else { 1.0 };
+        let mut clear = entered || d0.abs() >= self.epsilon;
+        if !clear && self.gradient(&start).dot(*r.direction()) < 0.0 {
+            side = -1.0;
+        }
+
+        for _ in 0..self.max_steps {
+            let d = side * self.sdf.distance(&r.at(t));
+            if d < self.epsilon {
+                if clear {
+                    break;
+                }
+            } else {
+                clear = true;
+            }
+
+            t += d.abs().max(self.epsilon) / dir_length;
+            if t > span.max {
+                return false;
+            }
+        }
+
+        if !clear || !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let p = r.at(t);
+        if (self.sdf.distance(&p)).abs() > 10.0 * self.epsilon {
+            // Ran out of steps before converging.
+            return false;
+        }
+
+        let outward_normal = self.gradient(&p).unit();
+        rec.t = t;
+        rec.p = p;
+        rec.mat = self.mat;
+        rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        rec.dpdu = Vec3::zero();
+        rec.dpdv = Vec3::zero();
+        rec.shading = None;
+
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+impl HittableClone for SdfShape {
+    fn clone_box(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sphere_matches_analytic() {
+        let shape = SdfShape::new(Arc::new(SdfSphere::new(Point3::new(0.0, 0.0, -5.0), 1.0)), None);
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::default();
+
+        assert!(shape.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-3);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_ray_leaving_surface() {
+        // A ray starting inside must find the far side instead of the point it started on.
+        let shape = SdfShape::new(Arc::new(SdfBox::new(Point3::zero(), Vec3::new(1.0, 1.0, 1.0))), None);
+        let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::default();
+
+        assert!(shape.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-3);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn test_miss() {
+        let shape = SdfShape::new(Arc::new(SdfTorus::new(Point3::zero(), 2.0, 0.5)), None);
+        let r = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rec = HitRecord::default();
+
+        // Straight down the hole of the torus.
+        assert!(!shape.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+    }
+}
@@ -2,17 +2,20 @@ use crate::hit::{Hittable, HitRecord, HittableClone};
 use crate::vec3::Vec3;
 use crate::ray::Ray;
 use crate::interval::Interval;
-use crate::material::Material;
+use crate::material::{MaterialId, Materials};
 use crate::aabb::AABB;
+use crate::hit_list::HittableList;
+use crate::primitive::Primitive;
 use std::sync::Arc;
 use crate::onb::ONB;
 use crate::rtweekend::random_double;
+use crate::stats::{count, Counter};
 
 #[derive(Clone)]
 pub struct Sphere {
     center1: Vec3,
     radius: f64,
-    mat: Option<Arc<dyn Material + Send + Sync>>,
+    mat: Option<MaterialId>,
     is_moving: bool,
     center_vec: Vec3,
     bbox: AABB,
@@ -20,7 +23,7 @@ pub struct Sphere {
 
 impl Sphere {
     // Stationary Sphere
-    pub fn new(center: Vec3, radius: f64, mat: Option<Arc<dyn Material + Send + Sync>>) -> Self {
+    pub fn new(center: Vec3, radius: f64, mat: Option<MaterialId>) -> Self {
         let center_vec = Vec3::new(radius, radius, radius);
         let bbox = AABB::from_points(center - center_vec, center + center_vec);   
         Self {
@@ -34,7 +37,7 @@ impl Sphere {
     }
 
     // Moving Sphere
-    pub fn new_moving(center1: Vec3, center2: Vec3, radius: f64, mat: Option<Arc<dyn Material + Send + Sync>>) -> Self {
+    pub fn new_moving(center1: Vec3, center2: Vec3, radius: f64, mat: Option<MaterialId>) -> Self {
         let center_vec = center2 - center1;
         let bbox = AABB::from_aabbs(
             &AABB::from_points(center1 - Vec3::new(radius, radius, radius), center1 + Vec3::new(radius, radius, radius)),
@@ -51,7 +54,7 @@ impl Sphere {
     }
 
     fn sphere_center(&self, time: f64) -> Vec3 {
-        self.center1 + self.center_vec * time.clone()
+        self.center1 + self.center_vec * time
     }
 
     pub fn get_sphere_uv(p: Vec3) -> (f64, f64) {
@@ -70,6 +73,20 @@ impl Sphere {
 
         (u, v)
     }
+    pub fn get_sphere_dpduv(p: Vec3, radius: f64) -> (Vec3, Vec3) {
+        // Derivatives of the surface point with respect to the (u, v) of get_sphere_uv,
+        // for a point p on the unit sphere and a sphere of the given radius.
+        let pi = std::f64::consts::PI;
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + pi;
+        let (sin_theta, cos_theta) = (theta.sin(), theta.cos());
+        let (sin_phi, cos_phi) = (phi.sin(), phi.cos());
+
+        let dpdu = 2.0 * pi * radius * Vec3::new(sin_theta * sin_phi, 0.0, sin_theta * cos_phi);
+        let dpdv = pi * radius * Vec3::new(-cos_theta * cos_phi, sin_theta, cos_theta * sin_phi);
+        (dpdu, dpdv)
+    }
+
     pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
         let r1 = random_double(0.0, 1.0);
         let r2 = random_double(0.0, 1.0);
@@ -85,12 +102,13 @@ impl Sphere {
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        count(Counter::PrimitiveTest);
         let center = if self.is_moving {
             self.sphere_center(r.time())
         } else {
             self.center1
         };
-        let oc = center - r.origin().clone();
+        let oc = center - *r.origin();
         let a = r.direction().squared_length();
         let h = Vec3::dot(r.direction(), oc);
         let c = oc.squared_length() - self.radius * self.radius;
@@ -113,10 +131,12 @@ impl Hittable for Sphere {
 
         rec.t = root;
         rec.p = r.at(rec.t);
-        rec.mat = self.mat.clone();
+        rec.mat = self.mat;
         let outward_normal = (rec.p - center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
         (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        (rec.dpdu, rec.dpdv) = Sphere::get_sphere_dpduv(outward_normal, self.radius);
+        rec.shading = None;
 
         true
     }
@@ -128,7 +148,7 @@ impl Hittable for Sphere {
         // This method only works for stationary spheres.
 
         let mut rec = HitRecord::default();
-        if !self.hit(&Ray::new(origin.clone(), direction.clone(), 0.0), Interval::with_values(0.001, f64::INFINITY), &mut rec) {
+        if !self.hit(&Ray::new(*origin, *direction, 0.0), Interval::with_values(0.001, f64::INFINITY), &mut rec) {
             return 0.0;
         }
 
@@ -139,15 +159,143 @@ impl Hittable for Sphere {
     }
 
     fn random(&self, origin: &Vec3) -> Vec3 {
-        let direction = self.center1 - origin.clone();
+        let direction = self.center1 - *origin;
         let distance_squared = direction.squared_length();
         let uvw = ONB::build_from_w(&direction);
         uvw.local_vec3(&Sphere::random_to_sphere(self.radius, distance_squared))
     }
+
+    fn collect_emitters(&self, materials: &Materials, lights: &mut HittableList) {
+        if self.mat.is_some_and(|mat| materials.get(mat).is_emissive()) {
+            lights.add(self.clone_box());
+        }
+    }
+
+    fn emitted_power(&self, materials: &Materials) -> f64 {
+        // Diffuse emission from the whole surface: pi * radiance per unit area.
+        let radiance = match self.mat {
+            Some(mat) => materials.get(mat).emission_estimate(),
+            None => return 0.0,
+        };
+        let area = 4.0 * std::f64::consts::PI * self.radius * self.radius;
+        std::f64::consts::PI * area * (radiance.x + radiance.y + radiance.z) / 3.0
+    }
+
+    fn sample_surface(&self) -> Option<(Vec3, Vec3)> {
+        // Like pdf_value, this only works for stationary spheres.
+        let normal = Vec3::random_unit_vector();
+        Some((self.center1 + self.radius * normal, normal))
+    }
+
+    fn as_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::Sphere(self.clone()))
+    }
 }
 
 impl HittableClone for Sphere {
     fn clone_box(&self) -> Arc<dyn Hittable + Sync + Send> {
         Arc::new(self.clone())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtweekend::seed_random;
+
+    #[test]
+    fn test_random_rays_hit_the_surface() {
+        seed_random(451);
+        for _ in 0..1000 {
+            let center = Vec3::random(-5.0, 5.0);
+            let radius = random_double(0.1, 3.0);
+            let sphere = Sphere::new(center, radius, None);
+
+            // From outside, aimed at a point on the sphere facing the origin of the ray.
+            let origin = center + (radius + random_double(0.1, 10.0)) * Vec3::random_unit_vector();
+            let target = center + radius * (origin - center).unit().random_on_hemisphere();
+            let r = Ray::new(origin, (target - origin) * random_double(0.1, 10.0), 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(sphere.hit(&r, Interval::with_values(0.001, f64::INFINITY), &mut rec));
+            assert!((rec.p - r.at(rec.t)).length() < 1e-9);
+            assert!(((rec.p - center).length() - radius).abs() < 1e-9);
+            assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+            assert!((rec.normal - (rec.p - center) / radius).length() < 1e-9);
+            assert!(rec.front_face);
+            assert!(rec.normal.dot(*r.direction()) < 0.0);
+            assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v));
+            assert!(sphere.bounding_box().contains(rec.p, 1e-9));
+
+            // The nearest of the two crossings: nothing of the sphere lies before it on the ray.
+            let mut nearer = HitRecord::default();
+            assert!(!sphere.hit(&r, Interval::with_values(0.001, rec.t - 1e-9), &mut nearer));
+        }
+    }
+
+    #[test]
+    fn test_rays_from_inside_hit_the_back_face() {
+        seed_random(452);
+        for _ in 0..1000 {
+            let center = Vec3::random(-5.0, 5.0);
+            let radius = random_double(0.1, 3.0);
+            let sphere = Sphere::new(center, radius, None);
+            let origin = center + random_double(0.0, 0.9 * radius) * Vec3::random_unit_vector();
+            let r = Ray::new(origin, Vec3::random_unit_vector(), 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(sphere.hit(&r, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+            assert!(((rec.p - center).length() - radius).abs() < 1e-9);
+            assert!(!rec.front_face);
+            // The normal faces back against the ray, into the sphere.
+            assert!(rec.normal.dot(*r.direction()) < 0.0);
+            assert!((rec.normal + (rec.p - center) / radius).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_rays_passing_by_miss() {
+        seed_random(453);
+        for _ in 0..1000 {
+            let center = Vec3::random(-5.0, 5.0);
+            let radius = random_double(0.1, 3.0);
+            let sphere = Sphere::new(center, radius, None);
+
+            // Along a line whose closest approach to the centre is outside the sphere.
+            let axis = Vec3::random_unit_vector();
+            let offset = (axis.cross(Vec3::random_unit_vector())).unit() * radius * random_double(1.01, 3.0);
+            let r = Ray::new(center + offset - 10.0 * axis, axis, 0.0);
+
+            let mut rec = HitRecord::default();
+            assert!(!sphere.hit(&r, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+        }
+    }
+
+    #[test]
+    fn test_sphere_uv() {
+        let cases = [
+            (Vec3::new(1.0, 0.0, 0.0), (0.5, 0.5)),
+            (Vec3::new(-1.0, 0.0, 0.0), (0.0, 0.5)),
+            (Vec3::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Vec3::new(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (Vec3::new(0.0, 0.0, 1.0), (0.25, 0.5)),
+            (Vec3::new(0.0, 0.0, -1.0), (0.75, 0.5)),
+        ];
+        for (p, (u, v)) in cases {
+            let (got_u, got_v) = Sphere::get_sphere_uv(p);
+            assert!((got_u - u).abs() < 1e-12 && (got_v - v).abs() < 1e-12, "{:?} gave {} {}", (p.x, p.y, p.z), got_u, got_v);
+        }
+    }
+
+    #[test]
+    fn test_moving_sphere_hits_where_it_is_at_the_ray_time() {
+        let sphere = Sphere::new_moving(Vec3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 0.0, 0.0), 1.0, None);
+        for time in [0.0, 0.25, 0.5, 1.0] {
+            let r = Ray::new(Vec3::new(4.0 * time, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), time);
+            let mut rec = HitRecord::default();
+            assert!(sphere.hit(&r, Interval::with_values(0.0, f64::INFINITY), &mut rec));
+            assert!((rec.t - 4.0).abs() < 1e-9);
+            assert!(sphere.bounding_box().contains(rec.p, 1e-9));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::framebuffer::FrameBuffer;
+use crate::scheduler::Tile;
+use crate::vec3::Vec3;
+type Color = Vec3;
+use std::cell::Cell;
+use std::fmt;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+// Events counted while rendering, to see where the time goes.
+#[derive(Clone, Copy, Debug)]
+pub enum Counter {
+    PrimaryRay, // Rays from the camera
+    SecondaryRay, // Rays continuing a path after a bounce, or starting one from a light
+    ShadowRay, // Visibility tests between two points
+    BvhNode, // BVH nodes whose box was tested
+    PrimitiveTest, // Intersection tests against spheres, quads and other shapes
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Counters {
+    pub primary_rays: u64,
+    pub secondary_rays: u64,
+    pub shadow_rays: u64,
+    pub bvh_nodes: u64,
+    pub primitive_tests: u64,
+}
+
+impl AddAssign for Counters {
+    fn add_assign(&mut self, other: Counters) {
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.bvh_nodes += other.bvh_nodes;
+        self.primitive_tests += other.primitive_tests;
+    }
+}
+
+thread_local! {
+    // Each thread counts on its own, so counting costs no more than an increment.
+    static COUNTS: [Cell<u64>; 5] = const { [Cell::new(0), Cell::new(0), Cell::new(0), Cell::new(0), Cell::new(0)] };
+}
+
+pub fn count(counter: Counter) {
+    COUNTS.with(|counts| {
+        let count = &counts[counter as usize];
+        count.set(count.get() + 1);
+    });
+}
+
+pub fn take() -> Counters {
+    // This thread's counts since the last take, starting the counts again from zero.
+    COUNTS.with(|counts| Counters {
+        primary_rays: counts[Counter::PrimaryRay as usize].take(),
+        secondary_rays: counts[Counter::SecondaryRay as usize].take(),
+        shadow_rays: counts[Counter::ShadowRay as usize].take(),
+        bvh_nodes: counts[Counter::BvhNode as usize].take(),
+        primitive_tests: counts[Counter::PrimitiveTest as usize].take(),
+    })
+}
+
+// What a render did: the counts over all threads, the time each tile took, and the time spent on
+// every pixel of the image.
+#[derive(Clone)]
+pub struct RenderStats {
+    pub counters: Counters,
+    pub tile_times: Vec<(Tile, Duration)>, // One entry for each tile of each pass
+    pub elapsed: Duration, // Wall clock time of the whole render
+    width: u32,
+    height: u32,
+    pixel_costs: Vec<f64>, // Seconds spent on each pixel, row by row
+}
+
+impl RenderStats {
+    pub fn new(width: u32, height: u32) -> Self {
+        RenderStats {
+            counters: Counters::default(),
+            tile_times: Vec::new(),
+            elapsed: Duration::ZERO,
+            width,
+            height,
+            pixel_costs: vec![0.0; width as usize * height as usize],
+        }
+    }
+
+    pub fn add_tile(&mut self, tile: &Tile, time: Duration, counters: Counters, pixel_costs: &[f64]) {
+        // pixel_costs holds the seconds spent on the tile's pixels, row by row.
+        self.counters += counters;
+        self.tile_times.push((*tile, time));
+        for (k, cost) in pixel_costs.iter().enumerate() {
+            let k = k as u32;
+            let index = self.index(tile.x0 + k % tile.width(), tile.y0 + k / tile.width());
+            self.pixel_costs[index] += cost;
+        }
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        j as usize * self.width as usize + i as usize
+    }
+
+    pub fn pixel_cost(&self, i: u32, j: u32) -> f64 {
+        self.pixel_costs[self.index(i, j)]
+    }
+
+    pub fn heatmap(&self) -> FrameBuffer {
+        // Cost of every pixel in false colour, from blue for the cheapest through green and yellow
+        // to red for the dearest. The scale tops out at the 99th percentile, so that a few very
+        // slow pixels don't leave the rest of the image all blue.
+        let mut sorted = self.pixel_costs.clone();
+        sorted.sort_by(f64::total_cmp);
+        let top = sorted.get(sorted.len() * 99 / 100).copied().unwrap_or(0.0);
+
+        let mut image = FrameBuffer::new(self.width, self.height);
+        for j in 0..self.height {
+            for i in 0..self.width {
+                let cost = if top > 0.0 { (self.pixel_cost(i, j) / top).min(1.0) } else { 0.0 };
+                image.set(i, j, false_colour(cost));
+            }
+        }
+        image
+    }
+}
+
+fn false_colour(t: f64) -> Color {
+    // Blue, cyan, green, yellow, red along t from 0 to 1, squared so that it comes out as these
+    // colours once gamma corrected for display.
+    let stops = [Color::new(0.0, 0.0, 1.0), Color::new(0.0, 1.0, 1.0), Color::new(0.0, 1.0, 0.0), Color::new(1.0, 1.0, 0.0), Color::new(1.0, 0.0, 0.0)];
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let k = (x as usize).min(stops.len() - 2);
+    let colour = stops[k] * (1.0 - (x - k as f64)) + stops[k + 1] * (x - k as f64);
+    colour * colour
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        let c = &self.counters;
+        let rays = c.primary_rays + c.secondary_rays + c.shadow_rays;
+        writeln!(f, "Rendered in {:.2} s", seconds)?;
+        writeln!(f, "  primary rays      {:>16}", c.primary_rays)?;
+        writeln!(f, "  secondary rays    {:>16}", c.secondary_rays)?;
+        writeln!(f, "  shadow rays       {:>16}", c.shadow_rays)?;
+        writeln!(f, "  BVH nodes visited {:>16}", c.bvh_nodes)?;
+        writeln!(f, "  primitive tests   {:>16}", c.primitive_tests)?;
+        if seconds > 0.0 {
+            writeln!(f, "  rays per second   {:>16.0}", rays as f64 / seconds)?;
+        }
+
+        if let (Some(fastest), Some(slowest)) = (self.tile_times.iter().min_by_key(|(_, time)| *time), self.tile_times.iter().max_by_key(|(_, time)| *time)) {
+            let total: Duration = self.tile_times.iter().map(|(_, time)| *time).sum();
+            writeln!(f, "  tiles             {:>16}", self.tile_times.len())?;
+            writeln!(f, "  mean tile         {:>14.3} s", total.as_secs_f64() / self.tile_times.len() as f64)?;
+            writeln!(f, "  fastest tile      {:>14.3} s at {}, {}", fastest.1.as_secs_f64(), fastest.0.x0, fastest.0.y0)?;
+            write!(f, "  slowest tile      {:>14.3} s at {}, {}", slowest.1.as_secs_f64(), slowest.0.x0, slowest.0.y0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_resets_counts() {
+        take();
+        count(Counter::PrimaryRay);
+        count(Counter::BvhNode);
+        count(Counter::BvhNode);
+        let counters = take();
+        assert_eq!(counters, Counters { primary_rays: 1, bvh_nodes: 2, ..Counters::default() });
+        assert_eq!(take(), Counters::default());
+    }
+
+    #[test]
+    fn test_heatmap_runs_blue_to_red() {
+        let mut stats = RenderStats::new(4, 1);
+        let tile = Tile { x0: 0, x1: 4, y0: 0, y1: 1 };
+        stats.add_tile(&tile, Duration::from_millis(4), Counters::default(), &[0.0, 1.0, 2.0, 4.0]);
+
+        let heatmap = stats.heatmap();
+        assert_eq!(heatmap.get(0, 0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(heatmap.get(2, 0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(heatmap.get(3, 0), Color::new(1.0, 0.0, 0.0));
+    }
+}
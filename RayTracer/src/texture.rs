@@ -1,12 +1,17 @@
-use crate::interval::Interval;
 use crate::vec3::Vec3;
 type Color = Vec3;
-use crate::rtw::RtwImage;
+use crate::rtw::{texel_color, ImageLoadError, MipMap, RtwImage, Texel, TextureFilter, WrapMode};
+use std::path::Path;
 use crate::perlin::Perlin;
+use crate::hit::HitRecord;
 use std::sync::Arc;
 
 pub trait Texture {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Color;
+    // Lookup at a hit, where textures that filter can use the hit's uv footprint.
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 #[derive(Clone)]
@@ -18,6 +23,7 @@ impl SolidColor {
     pub fn new(albedo: Color) -> Self {
         SolidColor { albedo }
     }
+    #[allow(clippy::self_named_constructors)]
     pub fn solid_color(red: f64, green: f64, blue: f64) -> Self {
         let albedo = Color::new(red, green, blue);
         SolidColor::new(albedo)
@@ -54,46 +60,156 @@ impl CheckerTexture {
     }
 }
 
-impl Texture for CheckerTexture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+impl CheckerTexture {
+    fn is_even(&self, p: &Vec3) -> bool {
         let x_integer = (self.inv_scale * p.x()).floor() as i32;
         let y_integer = (self.inv_scale * p.y()).floor() as i32;
         let z_integer = (self.inv_scale * p.z()).floor() as i32;
 
-        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+        (x_integer + y_integer + z_integer) % 2 == 0
+    }
+}
 
-        if is_even {
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        if self.is_even(p) {
             self.even.value(u, v, p)
         } else {
             self.odd.value(u, v, p)
         }
     }
+
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        if self.is_even(&rec.p) {
+            self.even.value_filtered(rec)
+        } else {
+            self.odd.value_filtered(rec)
+        }
+    }
 }
 
+// Checkers laid out in the surface's (u, v) rather than in world space, so they follow the surface.
 #[derive(Clone)]
-pub struct ImageTexture {
-    image: RtwImage,
+pub struct UvCheckerTexture {
+    frequency: (f64, f64), // Number of squares across u and v
+    even: Arc<dyn Texture + Send + Sync>,
+    odd: Arc<dyn Texture + Send + Sync>,
 }
 
-impl ImageTexture {
-    pub fn new(filename: &str) -> Self {
-        let image = RtwImage::new(filename);
-        ImageTexture { image }
+impl UvCheckerTexture {
+    pub fn from_texture(frequency: (f64, f64), even: Arc<dyn Texture + Send + Sync>, odd: Arc<dyn Texture + Send + Sync>) -> Self {
+        UvCheckerTexture { frequency, even, odd }
+    }
+
+    pub fn from_color(frequency: (f64, f64), c1: Color, c2: Color) -> Self {
+        UvCheckerTexture::from_texture(frequency, Arc::new(SolidColor::new(c1)), Arc::new(SolidColor::new(c2)))
+    }
+
+    fn is_even(&self, u: f64, v: f64) -> bool {
+        let u_integer = (self.frequency.0 * u).floor() as i64;
+        let v_integer = (self.frequency.1 * v).floor() as i64;
+
+        (u_integer + v_integer).rem_euclid(2) == 0
     }
 }
 
-impl Texture for ImageTexture {
+impl Texture for UvCheckerTexture {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
-        if self.image.height <= 0 {
-            Color::new(0.0, 1.0, 1.0) // Return solid cyan as a debugging aid
+        if self.is_even(u, v) {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        if self.is_even(rec.u, rec.v) {
+            self.even.value_filtered(rec)
         } else {
-            (1.0 / 255.0) * self.image.get_color(u, v)
+            self.odd.value_filtered(rec)
         }
     }
 }
 
-unsafe impl Send for ImageTexture {} 
-unsafe impl Sync for ImageTexture {}
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: MipMap,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    uv_scale: (f64, f64),
+    uv_offset: (f64, f64),
+}
+
+impl ImageTexture {
+    // A file that cannot be loaded leaves the texture solid cyan; load hands back the error instead.
+    pub fn new(filename: &str) -> Self {
+        ImageTexture::from_image(RtwImage::load(filename).unwrap_or_else(|_| RtwImage::empty()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ImageLoadError> {
+        Ok(ImageTexture::from_image(RtwImage::load(path)?))
+    }
+
+    pub fn from_image(image: RtwImage) -> Self {
+        ImageTexture {
+            image: MipMap::new(image),
+            filter: TextureFilter::Nearest,
+            wrap: WrapMode::Clamp,
+            uv_scale: (1.0, 1.0),
+            uv_offset: (0.0, 0.0),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        if matches!(filter, TextureFilter::Trilinear | TextureFilter::Anisotropic(_)) {
+            self.image.build_levels();
+        }
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    // Texture coordinates become (u * scale.0 + offset.0, v * scale.1 + offset.1),
+    // so a scale of (4, 4) with Repeat tiles the image four times each way.
+    pub fn with_uv_transform(mut self, scale: (f64, f64), offset: (f64, f64)) -> Self {
+        self.uv_scale = scale;
+        self.uv_offset = offset;
+        self
+    }
+
+    // Coverage in [0,1] from the image's alpha channel, 1 for images without one.
+    pub fn alpha(&self, rec: &HitRecord) -> f64 {
+        self.sample(rec.u, rec.v, rec.footprint.major, rec.footprint.minor)[3]
+    }
+
+    fn sample(&self, u: f64, v: f64, major: (f64, f64), minor: (f64, f64)) -> Texel {
+        if self.image.base().is_empty() {
+            return [0.0, 1.0, 1.0, 1.0]; // Return solid cyan as a debugging aid
+        }
+
+        let (su, sv) = self.uv_scale;
+        let u = u * su + self.uv_offset.0;
+        let v = v * sv + self.uv_offset.1;
+        let major = (major.0 * su, major.1 * sv);
+        let minor = (minor.0 * su, minor.1 * sv);
+
+        self.image.sample(u, v, major, minor, self.filter, self.wrap)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Color {
+        texel_color(self.sample(u, v, (0.0, 0.0), (0.0, 0.0)))
+    }
+
+    fn value_filtered(&self, rec: &HitRecord) -> Color {
+        texel_color(self.sample(rec.u, rec.v, rec.footprint.major, rec.footprint.minor))
+    }
+}
 
 #[derive(Clone)]
 pub struct NoiseTexture {
@@ -101,6 +217,12 @@ pub struct NoiseTexture {
     scale: f64,
 }
 
+impl Default for NoiseTexture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NoiseTexture {
     pub fn new() -> Self {
         NoiseTexture {
@@ -104,12 +104,12 @@ impl Vec3 {
     }
 
     pub fn reflect(&self, n: Vec3) -> Vec3 {
-        self.clone() - 2.0 * self.clone().dot(n) * n
+        *self - 2.0 * self.clone().dot(n) * n
     }
 
     pub fn refract(&self, n: Vec3, etai_over_etat: f64) -> Vec3 {
-        let cos_theta = (-1.0 * self.clone()).dot(n).min(1.0);
-        let r_out_perp = etai_over_etat * (self.clone() + cos_theta * n);
+        let cos_theta = (-1.0 * *self).dot(n).min(1.0);
+        let r_out_perp = etai_over_etat * (*self + cos_theta * n);
         let r_out_parallel = -((1.0 - r_out_perp.squared_length()).abs().sqrt()) * n;
         r_out_perp + r_out_parallel
     }
@@ -389,7 +389,7 @@ mod tests {
 
     #[test]
     fn test_squared_length() {
-        assert_eq!(Vec3::new(1.0, 2.0, 3.0).squared_length(), 14.0 as f64);
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0).squared_length(), 14.0_f64);
     }
 
     /*
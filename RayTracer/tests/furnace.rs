@@ -0,0 +1,120 @@
+// Furnace tests: a sphere of each material inside an environment that sends the same radiance from
+// every direction. Nothing may come back brighter than the light falling on it, and a material
+// that loses no energy, like a white diffuser, a perfect mirror or clear glass, must vanish into
+// the background. A normalization mistake in a material, or a wrong weight in the mixture of
+// light and material sampling, shows up as a sphere that glows or darkens.
+
+use raytracer::constant_medium::ConstantMedium;
+use raytracer::hit::Hittable;
+use raytracer::hit_list::HittableList;
+use raytracer::light_sampler::{LightSampler, LightSampling};
+use raytracer::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Materials, Metal};
+use raytracer::ray::Ray;
+use raytracer::rtweekend::{random_double, seed_random};
+use raytracer::sphere::Sphere;
+use raytracer::vec3::Vec3;
+type Point3 = Vec3;
+type Color = Vec3;
+use std::sync::Arc;
+
+const ENVIRONMENT: f64 = 1.0; // Radiance arriving from every direction
+const PATHS: usize = 20_000;
+const MAX_DEPTH: u32 = 50;
+const TOLERANCE: f64 = 0.02; // Largest difference allowed in the mean radiance of the sphere
+
+// Mean radiance of the paths from a viewpoint outside the unit sphere at the origin that hit it.
+// With a light, the environment is made up of the background and an emitting sphere of the same
+// radiance, so that it still looks uniform while paths sample it through the light PDF.
+fn furnace(object: Arc<dyn Hittable + Send + Sync>, materials: &Materials, with_light: bool) -> Color {
+    let mut materials = materials.clone();
+    let mut world = HittableList::new();
+    world.add(object);
+    if with_light {
+        let light = materials.add(DiffuseLight::with_color(Color::new(ENVIRONMENT, ENVIRONMENT, ENVIRONMENT)));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 3.0, 0.0), 0.5, Some(light))));
+    }
+    let mut emitters = HittableList::new();
+    for object in &world.objects {
+        object.collect_emitters(&materials, &mut emitters);
+    }
+    let lights = LightSampler::new(emitters, &materials, LightSampling::Uniform);
+    let background = Color::new(ENVIRONMENT, ENVIRONMENT, ENVIRONMENT);
+
+    let origin = Point3::new(0.0, 0.0, 5.0);
+    let mut sum = Color::zero();
+    for _ in 0..PATHS {
+        // Towards a point of the disc the sphere covers, seen from the viewpoint.
+        let target = loop {
+            let (x, y) = (random_double(-0.95, 0.95), random_double(-0.95, 0.95));
+            if x * x + y * y < 0.95 * 0.95 {
+                break Point3::new(x, y, 0.0);
+            }
+        };
+        let r = Ray::new(origin, target - origin, 0.0);
+        sum += r.ray_color(background, MAX_DEPTH, &world, &materials, &lights, &[]);
+    }
+    sum / PATHS as f64
+}
+
+// A unit sphere of mat, with the materials it needs.
+fn sphere(mat: impl Material + Send + Sync) -> (Arc<dyn Hittable + Send + Sync>, Materials) {
+    let mut materials = Materials::new();
+    let mat = materials.add(mat);
+    (Arc::new(Sphere::new(Point3::zero(), 1.0, Some(mat))), materials)
+}
+
+fn check(name: &str, (object, materials): (Arc<dyn Hittable + Send + Sync>, Materials), expected: f64) {
+    for with_light in [false, true] {
+        let mean = furnace(object.clone(), &materials, with_light);
+        for value in [mean.x, mean.y, mean.z] {
+            assert!(
+                value <= ENVIRONMENT + TOLERANCE,
+                "{} reflects {} of the {} it receives{}",
+                name,
+                value,
+                ENVIRONMENT,
+                if with_light { ", sampling the light" } else { "" }
+            );
+            assert!(
+                (value - expected).abs() < TOLERANCE,
+                "{} reflects {}, not {}{}",
+                name,
+                value,
+                expected,
+                if with_light { ", sampling the light" } else { "" }
+            );
+        }
+    }
+}
+
+#[test]
+fn test_lambertian() {
+    seed_random(47);
+    check("white Lambertian", sphere(Lambertian::new(Color::new(1.0, 1.0, 1.0))), ENVIRONMENT);
+    // Convex, so every bounce leaves for the environment and the albedo comes back once.
+    check("grey Lambertian", sphere(Lambertian::new(Color::new(0.5, 0.5, 0.5))), 0.5 * ENVIRONMENT);
+}
+
+#[test]
+fn test_metal() {
+    seed_random(48);
+    check("mirror", sphere(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0)), ENVIRONMENT);
+    check("brushed metal", sphere(Metal::new(Color::new(1.0, 1.0, 1.0), 0.3)), ENVIRONMENT);
+    check("tinted mirror", sphere(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0)), 0.8 * ENVIRONMENT);
+}
+
+#[test]
+fn test_dielectric() {
+    seed_random(49);
+    check("glass", sphere(Dielectric::new(1.5)), ENVIRONMENT);
+    check("diamond", sphere(Dielectric::new(2.4)), ENVIRONMENT);
+}
+
+#[test]
+fn test_isotropic_medium() {
+    seed_random(50);
+    let mut materials = Materials::new();
+    let fog = materials.add(Isotropic::new(Color::new(1.0, 1.0, 1.0)));
+    let boundary = Arc::new(Sphere::new(Point3::zero(), 1.0, None));
+    check("white fog", (Arc::new(ConstantMedium::new(boundary, 1.0, fog)), materials), ENVIRONMENT);
+}
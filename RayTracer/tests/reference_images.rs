@@ -0,0 +1,114 @@
+// Renders small, seeded versions of the built-in scenes and compares them with the reference
+// images in tests/reference, so that a change to a material, texture or the BVH that alters how a
+// scene looks is caught. After a change that is meant to alter the images, look them over and
+// write new references with
+//
+//     UPDATE_REFERENCES=1 cargo test --test reference_images
+
+use raytracer::camera::Integrator;
+use raytracer::rtweekend::seed_random;
+use raytracer::scenes;
+use std::path::PathBuf;
+
+const SEED: u64 = 2024;
+const SIZE: u32 = 32;
+const SAMPLES_PER_PIXEL: u32 = 16;
+const MAX_DEPTH: u32 = 8;
+
+// Largest root mean square difference allowed, over the 0 to 1 display values of every channel.
+const MAX_RMSE: f64 = 0.005;
+// Largest mean of the squared differences relative to the reference, which catches changes to dark
+// areas that are too small to move the RMSE.
+const MAX_RELATIVE_MSE: f64 = 0.0005;
+
+fn render(description: &str) -> image::RgbImage {
+    seed_random(SEED);
+    let mut scene = scenes::from_description(description).unwrap();
+    let camera = &mut scene.camera;
+    camera.set_image_size(SIZE, SIZE);
+    camera.samples_per_pixel = SAMPLES_PER_PIXEL;
+    camera.max_depth = camera.max_depth.min(MAX_DEPTH);
+    camera.passes = 1;
+    camera.seed = Some(SEED);
+    if let Integrator::PhotonMapping { nearest, radius, .. } = camera.integrator {
+        camera.integrator = Integrator::PhotonMapping { photons: 20_000, nearest, radius };
+    }
+    scene.render().to_rgb_image()
+}
+
+fn differences(image: &image::RgbImage, reference: &image::RgbImage) -> (f64, f64) {
+    // Root mean square and relative mean square differences.
+    let mut squared = 0.0;
+    let mut relative = 0.0;
+    for (a, b) in image.as_raw().iter().zip(reference.as_raw()) {
+        let (a, b) = (*a as f64 / 255.0, *b as f64 / 255.0);
+        squared += (a - b) * (a - b);
+        relative += (a - b) * (a - b) / (b * b + 0.01);
+    }
+    let n = image.as_raw().len() as f64;
+    ((squared / n).sqrt(), relative / n)
+}
+
+fn check(name: &str, description: &str) {
+    let image = render(description);
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "reference", &format!("{}.png", name)].iter().collect();
+
+    if std::env::var("UPDATE_REFERENCES").is_ok_and(|value| value == "1") {
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&path)
+        .unwrap_or_else(|e| panic!("no reference image {}: {}; run with UPDATE_REFERENCES=1 to make it", path.display(), e))
+        .to_rgb8();
+    assert_eq!(image.dimensions(), reference.dimensions(), "{} changed size", name);
+
+    let (rmse, relative_mse) = differences(&image, &reference);
+    if rmse > MAX_RMSE || relative_mse > MAX_RELATIVE_MSE {
+        let actual = path.with_file_name(format!("{}.actual.png", name));
+        let _ = image.save(&actual);
+        panic!(
+            "{} differs from its reference: RMSE {:.4} (at most {}), relative MSE {:.4} (at most {}); rendered image saved as {}",
+            name, rmse, MAX_RMSE, relative_mse, MAX_RELATIVE_MSE, actual.display()
+        );
+    }
+}
+
+macro_rules! reference_tests {
+    ($($name:ident: $description:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                check(stringify!($name), $description);
+            }
+        )*
+    };
+}
+
+reference_tests! {
+    bouncing_spheres: "bouncing_spheres",
+    checkered_spheres: "checkered_spheres",
+    earth: "earth",
+    perlin_spheres: "perlin_spheres",
+    quads: "quads",
+    simple_light: "simple_light",
+    cornell_box: "cornell_box",
+    cornell_box_bidirectional: "cornell_box bidirectional",
+    cornell_box_photons: "cornell_box photons",
+    cornell_smoke: "cornell_smoke",
+    final_scene: "final_scene 32 32 16 8",
+    sdf_shapes: "sdf_shapes",
+    csg_shapes: "csg_shapes",
+    tiled_floor: "tiled_floor",
+    bumpy_spheres: "bumpy_spheres",
+    procedural_textures: "procedural_textures",
+    texture_mapping: "texture_mapping",
+    delta_lights: "delta_lights",
+    many_lights: "many_lights",
+}
+
+#[test]
+fn test_seeded_renders_repeat() {
+    // The references only mean something if the same seed gives the same image.
+    assert_eq!(render("cornell_box").as_raw(), render("cornell_box").as_raw());
+}